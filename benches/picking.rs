@@ -0,0 +1,48 @@
+// Coolttviz is a binary, so we pull in the modules we want to measure directly.
+// We only use a little of each of them here, so the rest would all count as dead code,
+// along with the imports for their tests, which get switched on without the tests themselves.
+#[path = "../src/linalg.rs"]
+#[allow(dead_code, unused_imports)]
+mod linalg;
+#[path = "../src/geometry.rs"]
+#[allow(dead_code, unused_imports)]
+mod geometry;
+#[path = "../src/bvh.rs"]
+#[allow(dead_code, unused_imports)]
+mod bvh;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use nalgebra::{Point3, Vector3};
+
+fn picking(c: &mut Criterion) {
+    let mut group = c.benchmark_group("picking");
+    for dim in 4..=10 {
+        let names: Vec<String> = (0..dim).map(|i| format!("d{}", i)).collect();
+        let faces = geometry::faces(&names, 1.0, &[], &linalg::Projection::default());
+        let bvh = bvh::Bvh::build(&faces);
+
+        // A ray from the default camera position through one of the innermost faces,
+        // as the higher dimensions get projected down to a tiny region around the origin.
+        let origin = Point3::new(0.0, 0.0, 4.0);
+        let target = faces[faces.len() / 2].points.iter().sum::<Vector3<f32>>() / 4.0;
+        let dir = origin - Point3::from(target);
+
+        group.bench_with_input(BenchmarkId::new("brute_force", dim), &dim, |b, _| {
+            b.iter(|| {
+                faces.iter().enumerate()
+                    .filter_map(|(ix, face)| face.intersect(black_box(origin), black_box(dir)).map(|isect| (isect, ix)))
+                    .collect::<Vec<_>>()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("bvh", dim), &dim, |b, _| {
+            b.iter(|| bvh.intersections(&faces, black_box(origin), black_box(dir)))
+        });
+        group.bench_with_input(BenchmarkId::new("bvh_build", dim), &dim, |b, _| {
+            b.iter(|| bvh::Bvh::build(black_box(&faces)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, picking);
+criterion_main!(benches);
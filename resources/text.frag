@@ -0,0 +1,14 @@
+#version 140
+
+in vec2 v_tex_coords;
+in vec4 v_color;
+in float v_fill;
+out vec4 f_color;
+
+uniform sampler2D glyphs;
+
+void main() {
+    // Backgrounds are solid, whereas glyphs take their coverage from the atlas.
+    float coverage = max(texture(glyphs, v_tex_coords).r, v_fill);
+    f_color = vec4(v_color.rgb, v_color.a * coverage);
+}
@@ -0,0 +1,20 @@
+#version 140
+
+in vec2 position;
+in vec2 tex_coords;
+in vec4 color;
+in float fill;
+
+out vec2 v_tex_coords;
+out vec4 v_color;
+out float v_fill;
+
+// The size of the window in pixels, as text is laid out in window coordinates.
+uniform vec2 screen;
+
+void main() {
+  gl_Position = vec4(2.0 * position.x / screen.x - 1.0, 1.0 - 2.0 * position.y / screen.y, 0.0, 1.0);
+  v_tex_coords = tex_coords;
+  v_color = color;
+  v_fill = fill;
+}
@@ -0,0 +1,189 @@
+// Cooltt terms can get absolutely massive, so labels show an abbreviated version of them.
+// Everything here counts characters rather than bytes, as terms are full of things like 'λ', '→' and '𝕀'.
+
+// How many characters a label gets before we start cutting it short.
+pub const DEFAULT_WIDTH: usize = 10;
+
+const ELLIPSIS: char = '…';
+
+fn closing(open: char) -> Option<char> {
+    match open {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '⟨' => Some('⟩'),
+        _ => None
+    }
+}
+
+fn is_closing(c: char) -> bool {
+    matches!(c, ')' | ']' | '}' | '⟩')
+}
+
+fn len(s: &str) -> usize {
+    s.chars().count()
+}
+
+// Split a term into the pieces separated by whitespace outside of any brackets.
+fn tokens(term: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut depth: usize = 0;
+    let mut start = None;
+    for (ix, c) in term.char_indices() {
+        if c.is_whitespace() && depth == 0 {
+            if let Some(s) = start.take() {
+                tokens.push(&term[s..ix]);
+            }
+            continue;
+        }
+        if start.is_none() {
+            start = Some(ix);
+        }
+        if closing(c).is_some() {
+            depth += 1;
+        } else if is_closing(c) {
+            depth = depth.saturating_sub(1);
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(&term[s..]);
+    }
+    tokens
+}
+
+// If the token is wrapped in a matching pair of brackets, split it into the brackets and what is inside.
+fn bracketed(token: &str) -> Option<(char, &str, char)> {
+    let open = token.chars().next()?;
+    let close = closing(open)?;
+    if !token.ends_with(close) || len(token) < 2 {
+        return None;
+    }
+    // Make sure the first bracket is closed by the last one, and not somewhere in the middle, like in '(a) (b)'.
+    let mut depth = 0;
+    for (ix, c) in token.char_indices() {
+        if closing(c).is_some() {
+            depth += 1;
+        } else if is_closing(c) {
+            depth -= 1;
+            if depth == 0 && ix + c.len_utf8() < token.len() {
+                return None;
+            }
+        }
+    }
+    Some((open, &token[open.len_utf8()..token.len() - close.len_utf8()], close))
+}
+
+// Cut a string down to 'width' characters, marking that something has gone missing.
+fn truncate(s: &str, width: usize) -> String {
+    if len(s) <= width {
+        return s.to_string();
+    }
+    let mut short: String = s.chars().take(width.saturating_sub(1)).collect();
+    short.push(ELLIPSIS);
+    short
+}
+
+// Shorten a term to at most 'width' characters, only ever cutting it between tokens or inside of brackets.
+// We hang on to the head of an application for as long as we can, and elide the arguments, so that
+// 'f (g x) (h y z)' turns into 'f (…) (…)'.
+pub fn abbreviate(term: &str, width: usize) -> String {
+    let tokens = tokens(term);
+    let normalized = tokens.join(" ");
+    if len(&normalized) <= width {
+        return normalized;
+    }
+
+    match tokens.split_first() {
+        // A single bracketed term gets shortened on the inside.
+        Some((token, [])) => {
+            match bracketed(token) {
+                Some((open, inner, close)) if width > 2 => format!("{}{}{}", open, abbreviate(inner, width - 2), close),
+                _ => truncate(token, width)
+            }
+        },
+        Some((head, args)) => {
+            // Always leave room for at least ' …' after the head, so that a partial application never passes for a whole one.
+            let mut abbreviated = truncate(head, width.saturating_sub(2));
+            for (ix, arg) in args.iter().enumerate() {
+                // Arguments get replaced by an empty pair of their brackets, unless they are short enough to keep.
+                let short = match bracketed(arg) {
+                    Some((open, _, close)) => format!("{}{}{}", open, ELLIPSIS, close),
+                    None if len(arg) <= 3 => arg.to_string(),
+                    None => ELLIPSIS.to_string()
+                };
+                // Leave room for a trailing ellipsis, unless this is the last argument.
+                let room = if ix + 1 == args.len() { width } else { width.saturating_sub(2) };
+                if len(&abbreviated) + 1 + len(&short) > room {
+                    if len(&abbreviated) + 2 <= width {
+                        abbreviated.push(' ');
+                        abbreviated.push(ELLIPSIS);
+                    }
+                    break;
+                }
+                abbreviated.push(' ');
+                abbreviated.push_str(&short);
+            }
+            abbreviated
+        },
+        None => String::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_terms_are_untouched() {
+        assert_eq!(abbreviate("λ x → x", 10), "λ x → x");
+        assert_eq!(abbreviate("𝕀", 10), "𝕀");
+        assert_eq!(abbreviate("", 10), "");
+        // Runs of whitespace don't count against the width.
+        assert_eq!(abbreviate("f   x\n  y", 10), "f x y");
+    }
+
+    #[test]
+    fn elide_arguments() {
+        assert_eq!(abbreviate("f (g x) (h y z)", 10), "f (…) (…)");
+        assert_eq!(abbreviate("path (λ i → A i) (f a₀) (f a₁)", 15), "path (…) (…) …");
+        assert_eq!(abbreviate("ap f ⟨i, j⟩ [x ↦ 𝟙]", 12), "ap f ⟨…⟩ […]");
+    }
+
+    #[test]
+    fn shorten_inside_brackets() {
+        assert_eq!(abbreviate("(coe (λ i → A i) 0 1 a)", 14), "(coe (…) 0 …)");
+    }
+
+    #[test]
+    fn long_heads() {
+        assert_eq!(abbreviate("𝕀𝕀𝕀𝕀𝕀𝕀𝕀𝕀𝕀𝕀𝕀𝕀 x", 5), "𝕀𝕀… x");
+        assert_eq!(abbreviate("λλλλλλλλλλλλλλλ", 10), "λλλλλλλλλ…");
+    }
+
+    #[test]
+    fn heads_that_fill_the_width() {
+        assert_eq!(abbreviate("transport p a", 9), "transp… …");
+        assert_eq!(abbreviate("hcomp x y", 5), "hc… …");
+        assert_eq!(abbreviate("coerce (λ i → A i) 0 1 a", 6), "coe… …");
+        // A head that fits with room to spare still keeps what arguments it can.
+        assert_eq!(abbreviate("transport p a", 13), "transport p a");
+        assert_eq!(abbreviate("transport p a b", 13), "transport p …");
+    }
+
+    #[test]
+    fn never_too_wide() {
+        let terms = [
+            "λ 𝕚 𝕛 → hcom 𝕀 0 1 {∂ 𝕚 ∨ ∂ 𝕛} (λ 𝕜 → [𝕜=0 ∨ ∂ 𝕚 ↦ a, 𝕛=1 ↦ b])",
+            "((((((((((x))))))))))",
+            "→→→→ →→→→ →→→→ →→→→",
+            "ℕ × (𝔹 → 𝕊¹) ⊎ Σ (x : A) (B x)",
+            "f )( unbalanced ((",
+        ];
+        for term in terms.iter() {
+            for width in 1..40 {
+                let short = abbreviate(term, width);
+                assert!(len(&short) <= width, "{:?} abbreviated to {:?} at width {}", term, short, width);
+            }
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use nalgebra::{Point3, Vector3};
+
+use crate::geometry::Face;
+
+// How many faces we are willing to test by hand before splitting a node.
+const LEAF_SIZE: usize = 4;
+
+const PADDING: f32 = 1e-6;
+
+#[derive(Clone, Copy, Debug)]
+struct Aabb {
+    min: Vector3<f32>,
+    max: Vector3<f32>
+}
+
+impl Aabb {
+    fn empty() -> Aabb {
+        Aabb {
+            min: Vector3::repeat(f32::INFINITY),
+            max: Vector3::repeat(f32::NEG_INFINITY)
+        }
+    }
+
+    fn grow(&mut self, p: &Vector3<f32>) {
+        self.min = self.min.inf(p);
+        self.max = self.max.sup(p);
+    }
+
+    fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max)
+        }
+    }
+
+    fn face(face: &Face) -> Aabb {
+        let mut bounds = Aabb::empty();
+        for p in &face.points {
+            bounds.grow(p);
+        }
+        // The faces of high dimensional cubes get projected down to tiny sizes, where 'Face::intersect'
+        // starts to accept points that are a few ULPs outside the face. Pad the bounds so that we agree with it.
+        bounds.min -= Vector3::repeat(PADDING);
+        bounds.max += Vector3::repeat(PADDING);
+        bounds
+    }
+
+    // The standard slab test, for the ray 'origin + t * dir' with t >= 0.
+    fn hit(&self, origin: &Point3<f32>, inv_dir: &Vector3<f32>) -> bool {
+        let mut t_min = 0.0_f32;
+        let mut t_max = f32::INFINITY;
+        for axis in 0..3 {
+            let t0 = (self.min[axis] - origin[axis]) * inv_dir[axis];
+            let t1 = (self.max[axis] - origin[axis]) * inv_dir[axis];
+            // Written this way so that NaNs from rays parallel to a slab don't poison the interval.
+            t_min = t_min.max(t0.min(t1));
+            t_max = t_max.min(t0.max(t1));
+        }
+        t_min <= t_max
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Leaf { bounds: Aabb, start: usize, len: usize },
+    Branch { bounds: Aabb, left: usize, right: usize }
+}
+
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Node::Leaf { bounds, .. } => bounds,
+            Node::Branch { bounds, .. } => bounds
+        }
+    }
+}
+
+// A bounding volume hierarchy over the projected faces of a cube.
+// This only depends on the projected geometry, so it should be rebuilt whenever that changes.
+#[derive(Debug)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    // Face indices, permuted so that every leaf refers to a contiguous run.
+    indices: Vec<usize>
+}
+
+impl Bvh {
+    pub fn build(faces: &[Face]) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * faces.len() / LEAF_SIZE + 1),
+            indices: (0..faces.len()).collect()
+        };
+        let bounds: Vec<Aabb> = faces.iter().map(Aabb::face).collect();
+        let centroids: Vec<Vector3<f32>> = faces.iter().map(|face| face.points.iter().sum::<Vector3<f32>>() / 4.0).collect();
+        if !faces.is_empty() {
+            bvh.build_node(&bounds, &centroids, 0, faces.len());
+        }
+        bvh
+    }
+
+    // Build the node covering 'indices[start..start + len]', returning its index.
+    fn build_node(&mut self, bounds: &[Aabb], centroids: &[Vector3<f32>], start: usize, len: usize) -> usize {
+        let indices = &mut self.indices[start..start + len];
+        let node_bounds = indices.iter().fold(Aabb::empty(), |acc, ix| acc.union(&bounds[*ix]));
+
+        if len <= LEAF_SIZE {
+            self.nodes.push(Node::Leaf { bounds: node_bounds, start, len });
+            return self.nodes.len() - 1;
+        }
+
+        // Split at the median along the axis where the centroids are the most spread out.
+        let mut centroid_bounds = Aabb::empty();
+        for ix in indices.iter() {
+            centroid_bounds.grow(&centroids[*ix]);
+        }
+        let axis = (centroid_bounds.max - centroid_bounds.min).imax();
+        indices.sort_unstable_by(|a, b| centroids[*a][axis].partial_cmp(&centroids[*b][axis]).expect("Centroid should not be NaN"));
+
+        // Reserve our slot before building the children so that the root ends up at index 0.
+        let node = self.nodes.len();
+        self.nodes.push(Node::Leaf { bounds: node_bounds, start, len });
+        let mid = len / 2;
+        let left = self.build_node(bounds, centroids, start, mid);
+        let right = self.build_node(bounds, centroids, start + mid, len - mid);
+        self.nodes[node] = Node::Branch { bounds: node_bounds, left, right };
+        node
+    }
+
+    // Find every face hit by the ray, along with the intersection point.
+    // This uses the same conventions as 'Face::intersect', so 'dir' points back towards the viewer.
+    pub fn intersections(&self, faces: &[Face], origin: Point3<f32>, dir: Vector3<f32>) -> Vec<(Point3<f32>, usize)> {
+        let mut isects = Vec::new();
+        if self.nodes.is_empty() {
+            return isects;
+        }
+
+        let inv_dir = (-dir).map(|x| 1.0 / x);
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            if !node.bounds().hit(&origin, &inv_dir) {
+                continue;
+            }
+            match node {
+                Node::Leaf { start, len, .. } => {
+                    for ix in &self.indices[*start..*start + *len] {
+                        if let Some(isect) = faces[*ix].intersect(origin, dir) {
+                            isects.push((isect, *ix));
+                        }
+                    }
+                },
+                Node::Branch { left, right, .. } => {
+                    stack.push(*left);
+                    stack.push(*right);
+                }
+            }
+        }
+        isects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry;
+    use crate::geometry::fixtures::dim_names;
+    use crate::linalg::Projection;
+
+    #[test]
+    fn matches_brute_force() {
+        for (dim, projection) in (0..=8).flat_map(|dim| (0..Projection::NAMES.len()).map(move |ix| (dim, Projection::from_index(ix)))) {
+            let faces = geometry::faces(&dim_names(dim), 1.0, &[], &projection);
+            let bvh = Bvh::build(&faces);
+            let origin = Point3::new(0.3, 4.0, 2.5);
+            for target in &[Point3::new(0.0, 0.0, 0.0), Point3::new(0.2, -0.4, 0.1), Point3::new(5.0, 5.0, 5.0)] {
+                let dir = origin - target;
+                let mut expected: Vec<usize> = (0..faces.len()).filter(|ix| faces[*ix].intersect(origin, dir).is_some()).collect();
+                let mut actual: Vec<usize> = bvh.intersections(&faces, origin, dir).into_iter().map(|(_, ix)| ix).collect();
+                expected.sort_unstable();
+                actual.sort_unstable();
+                assert_eq!(expected, actual, "intersections with a {}-cube under {:?}", dim, projection);
+            }
+        }
+    }
+}
@@ -1,58 +1,352 @@
 use std::f32::consts::PI;
+use std::time::Instant;
 
-use nalgebra::{Isometry3, Vector3, Point3};
+use nalgebra::{Isometry3, Vector3, Point3, UnitQuaternion};
+
+use crate::transition;
+
+// How long it takes to swing the camera around to a new view, in seconds.
+const DURATION: f32 = 0.4;
+
+// How far the turntable lets you tip over the top, so that we never end up looking straight down the up vector.
+const MAX_POLAR: f32 = PI / 2.0 - 1e-3;
+
+// Where the camera is, and which way it's pointing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct View {
+    pub target: Point3<f32>,
+    pub radius: f32,
+    // Takes the camera's own axes to the world's. The camera sits along its own +z axis, looking back at the target.
+    pub orientation: UnitQuaternion<f32>,
+}
+
+struct Animation {
+    from: View,
+    to: View,
+    started: Instant,
+}
 
 // TODO: It might be a good idea to cache the eye and view?
 pub struct Camera {
-    radius: f32,
+    view: View,
+    // The turntable angles. These are only kept in sync with the orientation when we aren't in arcball mode.
     polar: f32,
     azimuth: f32,
+    // Arcball mode lets you tumble the camera freely, rather than keeping it upright.
+    arcball: bool,
+    animation: Option<Animation>,
+}
+
+fn turntable(azimuth: f32, polar: f32) -> UnitQuaternion<f32> {
+    let dir = Vector3::new(polar.cos() * azimuth.cos(), polar.sin(), polar.cos() * azimuth.sin());
+    UnitQuaternion::face_towards(&dir, &Vector3::y())
+}
+
+// The turntable angles that look the same way as 'orientation', ignoring any roll.
+fn turntable_angles(orientation: &UnitQuaternion<f32>) -> (f32, f32) {
+    let dir = orientation * Vector3::z();
+    (dir.z.atan2(dir.x), dir.y.clamp(-1.0, 1.0).asin().clamp(-MAX_POLAR, MAX_POLAR))
 }
 
+// Project a point in window coordinates onto the arcball, a unit sphere filling the middle of the window.
+fn arcball_point(pos: [f32; 2], screen: [f32; 2]) -> Vector3<f32> {
+    let scale = screen[0].min(screen[1]) / 2.0;
+    let x = (pos[0] - screen[0] / 2.0) / scale;
+    let y = (screen[1] / 2.0 - pos[1]) / scale;
+    let d = x * x + y * y;
+    if d <= 1.0 {
+        Vector3::new(x, y, (1.0 - d).sqrt())
+    } else {
+        Vector3::new(x, y, 0.0).normalize()
+    }
+}
+
+// The standard presets, keyed by name.
+pub const PRESETS: [&str; 4] = ["Front", "Top", "Side", "Isometric"];
+
 impl Camera {
     pub fn new() -> Camera {
         let azimuth = 90.0_f32.to_radians();
         let polar = 0.0;
         let radius = 4.0;
         Camera {
-            radius,
+            view: View {
+                target: Point3::origin(),
+                radius,
+                orientation: turntable(azimuth, polar),
+            },
             polar,
             azimuth,
+            arcball: false,
+            animation: None,
         }
     }
 
     pub fn rotate_azimuth(&mut self, delta: f32) {
+        self.animation = None;
         self.azimuth += delta;
+        self.view.orientation = turntable(self.azimuth, self.polar);
     }
 
     pub fn rotate_polar(&mut self, delta: f32) {
-        self.polar += delta;
+        self.animation = None;
+        self.polar = (self.polar + delta).clamp(-MAX_POLAR, MAX_POLAR);
+        self.view.orientation = turntable(self.azimuth, self.polar);
+    }
 
-        if self.polar < - PI/2.0 {
-            self.polar = -PI/2.0;
-        } else if self.polar > PI/2.0 {
-            self.polar = PI/2.0;
+    // Tumble the camera as the mouse moves from 'from' to 'to', both in window coordinates.
+    pub fn rotate_arcball(&mut self, from: [f32; 2], to: [f32; 2], screen: [f32; 2]) {
+        self.animation = None;
+        // Dragging should turn the scene along with the mouse, so the camera has to turn the other way.
+        if let Some(rotation) = UnitQuaternion::rotation_between(&arcball_point(to, screen), &arcball_point(from, screen)) {
+            self.view.orientation *= rotation;
         }
     }
 
+    // Slide the camera around in its own plane, by a number of pixels.
+    pub fn pan(&mut self, delta_x: f32, delta_y: f32) {
+        self.animation = None;
+        let scale = self.view.radius / 500.0;
+        let right = self.view.orientation * Vector3::x();
+        let up = self.view.orientation * Vector3::y();
+        self.view.target += up * delta_y * scale - right * delta_x * scale;
+    }
+
     pub fn zoom(&mut self, delta: f32) {
-        self.radius += delta;
-        if self.radius < 0.1 {
-            self.radius = 0.1;
+        self.animation = None;
+        self.view.radius += delta;
+        if self.view.radius < 0.1 {
+            self.view.radius = 0.1;
+        }
+    }
+
+    // Smoothly move over to a new view. Out of arcball mode, the camera always ends up standing upright.
+    pub fn animate_to(&mut self, mut to: View) {
+        if !self.arcball {
+            let (azimuth, polar) = turntable_angles(&to.orientation);
+            to.orientation = turntable(azimuth, polar);
+        }
+        self.animation = Some(Animation { from: self.view, to, started: Instant::now() });
+    }
+
+    // Jump straight to the end of any animation, for when there is nobody around to watch it.
+    pub fn finish_animation(&mut self) {
+        if let Some(animation) = self.animation.take() {
+            self.view = animation.to;
+        }
+        self.update();
+    }
+
+    pub fn arcball(&self) -> bool {
+        self.arcball
+    }
+
+    // The turntable always keeps the camera upright, so switching over to it swings out any roll left over from the arcball,
+    // rather than getting rid of it all at once.
+    pub fn set_arcball(&mut self, arcball: bool) {
+        if arcball == self.arcball {
+            return;
+        }
+        self.arcball = arcball;
+        if !arcball {
+            let to = self.animation.as_ref().map_or(self.view, |animation| animation.to);
+            self.animate_to(to);
+        }
+    }
+
+    pub fn current(&self) -> View {
+        self.view
+    }
+
+    // The current view, swung around to look from the given angles, in radians.
+    pub fn looking_from(&self, azimuth: f32, polar: f32) -> View {
+        View { orientation: turntable(azimuth, polar.clamp(-MAX_POLAR, MAX_POLAR)), ..self.view }
+    }
+
+    pub fn preset(&self, ix: usize) -> View {
+        match ix {
+            0 => self.looking_from(90.0_f32.to_radians(), 0.0),
+            1 => self.looking_from(90.0_f32.to_radians(), MAX_POLAR),
+            2 => self.looking_from(0.0, 0.0),
+            // Looking down the diagonal of the cube.
+            _ => self.looking_from(45.0_f32.to_radians(), (1.0 / 3.0_f32.sqrt()).asin())
+        }
+    }
+
+    pub fn azimuth(&self) -> f32 {
+        self.azimuth
+    }
+
+    pub fn polar(&self) -> f32 {
+        self.polar
+    }
+
+    // A view looking at 'points' from the current direction, backed off far enough that they all fit on screen.
+    pub fn fit(&self, points: &[Vector3<f32>], fov: f32) -> View {
+        if points.is_empty() {
+            return self.view;
+        }
+        let min = points.iter().fold(points[0], |acc, p| acc.inf(p));
+        let max = points.iter().fold(points[0], |acc, p| acc.sup(p));
+        let center = (min + max) / 2.0;
+        let radius = points.iter().map(|p| (p - center).norm()).fold(0.0_f32, f32::max).max(0.05);
+        View {
+            target: Point3::from(center),
+            // Leave a little room around the edges.
+            radius: 1.1 * radius / (fov / 2.0).sin(),
+            ..self.view
+        }
+    }
+
+    // Swing around to face 'point', keeping our distance.
+    pub fn focus(&mut self, point: Point3<f32>) {
+        self.animate_to(View { target: point, ..self.view });
+    }
+
+    // Move any animation along. This should get called once a frame.
+    pub fn update(&mut self) {
+        if let Some(animation) = &self.animation {
+            let t = animation.started.elapsed().as_secs_f32() / DURATION;
+            let s = transition::ease(t);
+            let (from, to) = (animation.from, animation.to);
+            self.view = View {
+                target: from.target + s * (to.target - from.target),
+                radius: from.radius + s * (to.radius - from.radius),
+                orientation: from.orientation.slerp(&to.orientation, s),
+            };
+            if t >= 1.0 {
+                self.view = to;
+                self.animation = None;
+            }
+        }
+
+        // Keep the turntable angles in sync, so that switching out of arcball mode carries on from the same direction.
+        if self.arcball || self.animation.is_none() {
+            let (azimuth, polar) = turntable_angles(&self.view.orientation);
+            self.azimuth = azimuth;
+            self.polar = polar;
         }
     }
 
     pub fn eye(&self) -> Point3<f32> {
-        Point3::new(
-            self.radius * self.polar.cos() * self.azimuth.cos(),
-            self.radius * self.polar.sin(),
-            self.radius * self.polar.cos() * self.azimuth.sin()
-        )
+        self.view.target + self.view.orientation * Vector3::new(0.0, 0.0, self.view.radius)
     }
 
     pub fn view(&self) -> Isometry3<f32> {
-        let origin : Point3<f32> = Point3::new(0.0, 0.0, 0.0);
-        let up : Vector3<f32> = Vector3::new(0.0, 1.0, 0.0);
-        Isometry3::look_at_rh(&self.eye(), &origin, &up)
+        let up = self.view.orientation * Vector3::y();
+        Isometry3::look_at_rh(&self.eye(), &self.view.target, &up)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_view() {
+        let camera = Camera::new();
+        assert!((camera.eye() - Point3::new(0.0, 0.0, 4.0)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn presets() {
+        let camera = Camera::new();
+        let eye = |view: View| view.target + view.orientation * Vector3::new(0.0, 0.0, view.radius);
+        assert!((eye(camera.preset(0)) - Point3::new(0.0, 0.0, 4.0)).norm() < 1e-5);
+        assert!((eye(camera.preset(1)) - Point3::new(0.0, 4.0, 0.0)).norm() < 1e-2);
+        assert!((eye(camera.preset(2)) - Point3::new(4.0, 0.0, 0.0)).norm() < 1e-5);
+        let iso = eye(camera.preset(3)).coords.normalize();
+        assert!((iso - Vector3::repeat(1.0 / 3.0_f32.sqrt())).norm() < 1e-5);
+    }
+
+    #[test]
+    fn arcball_goes_over_the_top() {
+        let mut camera = Camera::new();
+        camera.set_arcball(true);
+        // Dragging straight down across the middle of the window keeps tipping the camera over, without stopping at the pole.
+        for _ in 0..10 {
+            camera.rotate_arcball([400.0, 250.0], [400.0, 350.0], [800.0, 600.0]);
+            camera.update();
+        }
+        let up = camera.current().orientation * Vector3::y();
+        assert!(up.y < 0.0, "the camera should have flipped upside down, but up is {:?}", up);
+    }
+
+    #[test]
+    fn leaving_arcball_mode() {
+        let mut camera = Camera::new();
+        camera.set_arcball(true);
+        // Dragging around in a circle leaves the camera rolled over to one side.
+        for (from, to) in [([400.0, 200.0], [500.0, 300.0]), ([500.0, 300.0], [400.0, 400.0]), ([400.0, 400.0], [300.0, 300.0])].iter() {
+            camera.rotate_arcball(*from, *to, [800.0, 600.0]);
+            camera.update();
+        }
+        let rolled = camera.current();
+        let right = rolled.orientation * Vector3::x();
+        assert!(right.y.abs() > 1e-2, "the camera should be rolled, but right is {:?}", right);
+        let (azimuth, polar) = (camera.azimuth(), camera.polar());
+
+        // Nothing moves on the frame we switch over.
+        camera.set_arcball(false);
+        camera.update();
+        assert!(camera.current().orientation.angle_to(&rolled.orientation) < 1e-2);
+
+        // Once the roll has been swung out, we're looking the same way as before, only upright.
+        camera.finish_animation();
+        let upright = camera.current().orientation;
+        assert!((upright * Vector3::x()).y.abs() < 1e-4);
+        assert!((upright * Vector3::z() - rolled.orientation * Vector3::z()).norm() < 1e-4);
+        assert!((camera.azimuth() - azimuth).abs() < 1e-4 && (camera.polar() - polar).abs() < 1e-4);
+    }
+
+    #[test]
+    fn updating_stands_still() {
+        let mut camera = Camera::new();
+        camera.set_arcball(true);
+        camera.rotate_arcball([400.0, 200.0], [500.0, 300.0], [800.0, 600.0]);
+        let rolled = camera.current();
+        // Even when the view is rolled over, nothing moves unless we ask it to.
+        camera.arcball = false;
+        camera.update();
+        assert!(camera.animation.is_none());
+        assert_eq!(camera.current(), rolled);
+    }
+
+    #[test]
+    fn animations_stand_upright() {
+        let mut camera = Camera::new();
+        let rolled = View { orientation: camera.current().orientation * UnitQuaternion::from_axis_angle(&Vector3::z_axis(), 0.5), ..camera.current() };
+        camera.animate_to(rolled);
+        camera.finish_animation();
+        assert!(camera.animation.is_none());
+        assert!(camera.current().orientation.angle_to(&Camera::new().current().orientation) < 1e-4);
+    }
+
+    #[test]
+    fn zooming_stops_animations() {
+        let mut camera = Camera::new();
+        camera.animate_to(camera.preset(1));
+        camera.zoom(1.0);
+        assert!(camera.animation.is_none());
+        assert!((camera.current().radius - 5.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn turntable_angles_round_trip() {
+        let mut camera = Camera::new();
+        for &(azimuth, polar) in [(0.3, 0.2), (-2.0, -1.0), (3.0, 1.2)].iter() {
+            camera.animate_to(camera.looking_from(azimuth, polar));
+            camera.finish_animation();
+            assert!((camera.azimuth() - azimuth).abs() < 1e-4 && (camera.polar() - polar).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn fit_to_view() {
+        let camera = Camera::new();
+        let points = [Vector3::new(1.0, 1.0, 1.0), Vector3::new(3.0, -1.0, 1.0)];
+        let view = camera.fit(&points, 45.0_f32.to_radians());
+        assert!((view.target - Point3::new(2.0, 0.0, 1.0)).norm() < 1e-5);
+        assert!(view.radius > 2.0_f32.sqrt());
     }
 }
@@ -0,0 +1,102 @@
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage:
+    coolttviz                   Open a window, and wait for cooltt to send us goals.
+    coolttviz --render GOAL --out IMAGE [--size WxH]
+                                Draw GOAL to IMAGE without opening a window.
+                                IMAGE can be a .png, an .svg, or a .tex for TikZ,
+                                or .obj or .gltf for the cube as a 3D model.";
+
+// The same size as the window we open by default.
+const DEFAULT_SIZE: [u32; 2] = [1024, 768];
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Interactive,
+    Render { goal: PathBuf, out: PathBuf, size: [u32; 2] }
+}
+
+fn parse_size(size: &str) -> Result<[u32; 2], String> {
+    let bad = || format!("Expected a size like 800x600, but got '{}'", size);
+    let (width, height) = size.split_once(['x', 'X']).ok_or_else(bad)?;
+    match (width.parse(), height.parse()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok([width, height]),
+        _ => Err(bad())
+    }
+}
+
+// Work out what we have been asked to do. The arguments shouldn't include the name of the program.
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    if args.is_empty() {
+        return Ok(Command::Interactive);
+    }
+
+    let mut goal = None;
+    let mut out = None;
+    let mut size = DEFAULT_SIZE;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--render" => goal = Some(PathBuf::from(value()?)),
+            "--out" => out = Some(PathBuf::from(value()?)),
+            "--size" => size = parse_size(value()?)?,
+            _ => return Err(format!("Unknown argument '{}'", arg))
+        }
+    }
+
+    match (goal, out) {
+        (Some(goal), Some(out)) => Ok(Command::Render { goal, out, size }),
+        (Some(_), None) => Err("--render needs an --out to write to".to_string()),
+        _ => Err("--out and --size only make sense along with --render".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn render() {
+        assert_eq!(parse(&args(&[])), Ok(Command::Interactive));
+        assert_eq!(
+            parse(&args(&["--render", "goal.json", "--out", "goal.png", "--size", "640x480"])),
+            Ok(Command::Render { goal: "goal.json".into(), out: "goal.png".into(), size: [640, 480] })
+        );
+        assert_eq!(
+            parse(&args(&["--out", "goal.png", "--render", "goal.json"])),
+            Ok(Command::Render { goal: "goal.json".into(), out: "goal.png".into(), size: DEFAULT_SIZE })
+        );
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("800x600"), Ok([800, 600]));
+        assert_eq!(parse_size("800X600"), Ok([800, 600]));
+        assert_eq!(
+            parse(&args(&["--render", "goal.json", "--size", "1x2", "--out", "goal.svg", "--size", "3x4"])),
+            Ok(Command::Render { goal: "goal.json".into(), out: "goal.svg".into(), size: [3, 4] })
+        );
+    }
+
+    #[test]
+    fn bad_arguments() {
+        assert!(parse(&args(&["--render", "goal.json"])).is_err());
+        assert!(parse(&args(&["--render"])).is_err());
+        assert!(parse(&args(&["--size", "10x10"])).is_err());
+        assert!(parse(&args(&["--frobnicate"])).is_err());
+        assert_eq!(parse(&args(&["--render", "goal.json", "--out"])), Err("--out needs a value".to_string()));
+        assert_eq!(
+            parse(&args(&["--render", "goal.json", "--out", "goal.png", "--size", "big"])),
+            Err("Expected a size like 800x600, but got 'big'".to_string())
+        );
+        for size in ["", "10", "10x", "x10", "0x10", "-1x10", "10x10x10"].iter() {
+            assert!(parse_size(size).is_err(), "{:?} should not be a size", size);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use std::ffi::c_void;
+use std::rc::Rc;
+
+use glium::backend::{Backend, Context};
+use glium::debug::DebugCallbackBehavior;
+use glium::SwapBuffersError;
+use khronos_egl as egl;
+
+// From EGL_MESA_platform_surfaceless, which is too new for khronos-egl to know about.
+const PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+
+// An EGL context that doesn't draw to a window, or even to a surface. Everything gets drawn into framebuffers of our own.
+// Mesa falls back to llvmpipe when there isn't a GPU around, so this works without a display or a GPU, like OSMesa does.
+struct Surfaceless {
+    egl: egl::DynamicInstance<egl::EGL1_5>,
+    display: egl::Display,
+    context: egl::Context,
+    size: (u32, u32)
+}
+
+unsafe impl Backend for Surfaceless {
+    // There's nothing on screen to swap.
+    fn swap_buffers(&self) -> Result<(), SwapBuffersError> {
+        Ok(())
+    }
+
+    unsafe fn get_proc_address(&self, symbol: &str) -> *const c_void {
+        self.egl.get_proc_address(symbol).map_or(std::ptr::null(), |f| f as *const c_void)
+    }
+
+    fn get_framebuffer_dimensions(&self) -> (u32, u32) {
+        self.size
+    }
+
+    fn is_current(&self) -> bool {
+        self.egl.get_current_context() == Some(self.context)
+    }
+
+    unsafe fn make_current(&self) {
+        self.egl.make_current(self.display, None, None, Some(self.context)).expect("Failed to make the EGL context current");
+    }
+}
+
+impl Drop for Surfaceless {
+    fn drop(&mut self) {
+        let _ = self.egl.make_current(self.display, None, None, None);
+        let _ = self.egl.destroy_context(self.display, self.context);
+        let _ = self.egl.terminate(self.display);
+    }
+}
+
+// Set up a GL 3.3 context on Mesa's surfaceless platform. libEGL gets loaded at runtime, so we can still start up without it.
+pub fn surfaceless(width: u32, height: u32) -> Result<Rc<Context>, String> {
+    let egl = unsafe { egl::DynamicInstance::<egl::EGL1_5>::load_required() }.map_err(|err| format!("Couldn't load libEGL: {}", err))?;
+    let failed = |what: &'static str| move |err: egl::Error| format!("Failed to {}: {}", what, err);
+
+    let display = egl.get_platform_display(PLATFORM_SURFACELESS_MESA, egl::DEFAULT_DISPLAY, &[egl::ATTRIB_NONE]).map_err(failed("open the surfaceless display"))?;
+    egl.initialize(display).map_err(failed("initialize EGL"))?;
+    egl.bind_api(egl::OPENGL_API).map_err(failed("bind OpenGL"))?;
+    // Configs default to being for windows, which the surfaceless platform doesn't have any of.
+    let config = egl.choose_first_config(display, &[egl::SURFACE_TYPE, egl::PBUFFER_BIT, egl::RENDERABLE_TYPE, egl::OPENGL_BIT, egl::NONE])
+        .map_err(failed("choose a config"))?
+        .ok_or_else(|| "EGL doesn't have any configs that can do OpenGL".to_string())?;
+    let attribs = [
+        egl::CONTEXT_MAJOR_VERSION, 3,
+        egl::CONTEXT_MINOR_VERSION, 3,
+        egl::CONTEXT_OPENGL_PROFILE_MASK, egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+        egl::NONE
+    ];
+    let context = egl.create_context(display, config, None, &attribs).map_err(failed("create a context"))?;
+    egl.make_current(display, None, None, Some(context)).map_err(failed("make the context current"))?;
+
+    let backend = Surfaceless { egl, display, context, size: (width, height) };
+    unsafe { Context::new(backend, true, DebugCallbackBehavior::Ignore) }.map_err(|err| format!("Failed to initialize glium: {}", err))
+}
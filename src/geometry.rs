@@ -0,0 +1,572 @@
+use nalgebra::{Point3, Vector3};
+use ordered_float::NotNan;
+
+use crate::bvh::Bvh;
+use crate::linalg::{Project, Slice};
+
+// The dimensions fixed by a cell of the cube, and which endpoint they are fixed to.
+pub type Constraints = Vec<(String, bool)>;
+
+// Insert a zero bit at 'ix', shifting over the upper bits to compensate.
+fn insert_bit(bits : u32, ix : u32) -> u32 {
+    let upper_mask = u32::MAX << (ix + 1);
+    let upper = upper_mask & (bits << 1);
+    let lower_mask = (1 << ix) - 1;
+    let lower = lower_mask & bits;
+    upper | lower
+}
+
+fn slice(slices: &[Slice], ix: usize) -> Slice {
+    slices.get(ix).copied().unwrap_or_default()
+}
+
+// The dimensions that haven't been sliced away.
+fn free_dims(dim: usize, slices: &[Slice]) -> Vec<usize> {
+    (0..dim).filter(|ix| slice(slices, *ix).is_free()).collect()
+}
+
+// Turn the bits of a u32 into a n-dimensional point, where bit 'i' picks the endpoint of 'free[i]'.
+// Every other dimension is sitting wherever it has been sliced.
+fn point(bits: u32, free: &[usize], slices: &[Slice], dim: usize, size: f32) -> Vec<f32> {
+    let mut v: Vec<f32> = (0..dim).map(|ix| slice(slices, ix).coordinate(size).unwrap_or(0.0)).collect();
+    for (i, ix) in free.iter().enumerate() {
+        let b = if ((1 << i) & bits) == 0 {
+            -size
+        } else {
+            size
+        };
+        v[*ix] = b;
+    }
+    v
+}
+
+// Collect the constraints of a point, skipping over the dimensions that vary.
+// Dimensions that have been sliced somewhere in the middle of the interval aren't constrained to either endpoint,
+// so they get skipped too.
+fn dims_from_point(dim_names: &[String], point: &[f32], varying: &[usize], slices: &[Slice]) -> Constraints {
+    let mut dims = Vec::new();
+    for i in 0..dim_names.len() {
+        let d = point[i] > 0.0;
+        let endpoint = slice(slices, i).is_free() || slice(slices, i).endpoint().is_some();
+        if !varying.contains(&i) && endpoint {
+            dims.push((dim_names[i].clone(), d));
+        }
+    }
+    dims
+}
+
+// Distance from 'p' to the ray, along with the ray parameter of the closest point.
+// As with 'Face::intersect', the ray points back towards the viewer, so hits in front of us have t < 0.
+fn ray_distance(origin: Point3<f32>, dir: Vector3<f32>, p: &Vector3<f32>) -> (f32, f32) {
+    let t = (p - origin.coords).dot(&dir) / dir.norm_squared();
+    let closest = origin.coords + t * dir;
+    ((p - closest).norm(), t)
+}
+
+#[derive(Clone, Debug)]
+pub struct Face {
+    pub points: [Vector3<f32>; 4],
+    pub normal: Vector3<f32>,
+    // The pair of dimensions that vary across the face.
+    pub varying: [usize; 2],
+    pub dims: Constraints
+}
+
+impl Face {
+    // The points go bottom left, bottom right, top left, top right, where 'varying[0]' runs left to right.
+    pub fn new(points: [Vector3<f32>; 4], varying: [usize; 2], dims: Constraints) -> Face {
+        let horiz = points[1] - points[0];
+        let vert = points[2] - points[0];
+        let normal = horiz.cross(&vert);
+        Face { points, normal, varying, dims }
+    }
+
+    fn inside_out(v0 : &Vector3<f32>, v1 : &Vector3<f32>, q : &Point3<f32>, n : &Vector3<f32>) -> bool {
+        (v1 - v0).cross(&(q.coords - v0)).dot(n) >= 0.0
+    }
+
+    pub fn intersect(&self, origin: Point3<f32>, dir: Vector3<f32>) -> Option<Point3<f32>> {
+        // Compute the intersection point on the supporting plane of the face.
+        let dist = (self.points[0] - origin.coords).dot(&self.normal);
+        let angle = dir.dot(&self.normal);
+        let t = dist/angle;
+        let isect = origin + t*dir;
+        // We just generalize the standard inside-out test for a triangle here.
+        // We also check that t < 0 to ensure that we ignore any intersections that occur behind us.
+        if Face::inside_out(&self.points[0], &self.points[1], &isect, &self.normal)
+            && Face::inside_out(&self.points[1], &self.points[3], &isect, &self.normal)
+            && Face::inside_out(&self.points[3], &self.points[2], &isect, &self.normal)
+            && Face::inside_out(&self.points[2], &self.points[0], &isect, &self.normal)
+            && t < 0.0 {
+            Some(isect)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Edge {
+    pub points: [Vector3<f32>; 2],
+    // The dimension that varies along the edge.
+    pub varying: usize,
+    pub dims: Constraints
+}
+
+impl Edge {
+    pub fn intersect(&self, origin: Point3<f32>, dir: Vector3<f32>, radius: f32) -> Option<Point3<f32>> {
+        // Find the closest points between the ray and the supporting line of the edge,
+        // and then clamp that point to the edge itself.
+        let along = self.points[1] - self.points[0];
+        let w = self.points[0] - origin.coords;
+        let a = along.dot(&along);
+        let b = along.dot(&dir);
+        let c = dir.dot(&dir);
+        let denom = a * c - b * b;
+        let s = if denom.abs() < f32::EPSILON {
+            0.0
+        } else {
+            ((b * dir.dot(&w) - c * along.dot(&w)) / denom).clamp(0.0, 1.0)
+        };
+        let closest = self.points[0] + s * along;
+        let (dist, t) = ray_distance(origin, dir, &closest);
+        if dist <= radius && t < 0.0 {
+            Some(Point3::from(closest))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Corner {
+    pub point: Vector3<f32>,
+    pub dims: Constraints
+}
+
+impl Corner {
+    pub fn intersect(&self, origin: Point3<f32>, dir: Vector3<f32>, radius: f32) -> Option<Point3<f32>> {
+        let (dist, t) = ray_distance(origin, dir, &self.point);
+        if dist <= radius && t < 0.0 {
+            Some(Point3::from(self.point))
+        } else {
+            None
+        }
+    }
+}
+
+// Something underneath the mouse, referred to by its index in the cube.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pick {
+    Face(usize),
+    Edge(usize),
+    Corner(usize)
+}
+
+// How close (in world space) the mouse ray needs to get to a corner or edge to pick it.
+const PICK_RADIUS: f32 = 0.05;
+
+pub fn faces(dim_names: &[String], size: f32, slices: &[Slice], projection: &impl Project) -> Vec<Face> {
+    // We only build the faces of the slice, which is a cube over the free dimensions.
+    let free = free_dims(dim_names.len(), slices);
+    let dim = free.len() as u32;
+
+    // Goals with less than 2 dimensions don't have any faces at all.
+    if dim < 2 {
+        return Vec::new();
+    }
+
+    let mut faces = Vec::with_capacity(((dim * (dim - 1) / 2) * 2_u32.pow(dim - 2)) as usize);
+    // To build a 2-face for an n-cube, we will need to
+    // pick 2 dimensions that will vary to form all the corners
+    // of the square.
+    for d0 in 0..dim {
+        for d1 in d0+1..dim {
+            // Now that we know what 2 dimensions will vary, we need
+            // to pick where on the cube this 2-face will live.
+
+            // For instance, on a 3-cube, if we vary the 'x' and 'y' dimensions, we need to create
+            // faces when 'z' is 0 AND 1. To generalize to higher dimensions, we need
+            // to generate all possible places where the face can live by looking
+            // at all the dimensions that do not vary during face construction.
+            //
+            // To do this cheaply and easily, we will use some bit level-magic by
+            // realizing that an integer 'c < 2 ^ n' can represent a vertex on an
+            // n-cube by manner of it's binary representation.
+            let (a, b) = (free[d0 as usize], free[d1 as usize]);
+            for loc in 0..2_u32.pow(dim - 2) {
+                let mut v = point(insert_bit(insert_bit(loc, d0), d1), &free, slices, dim_names.len(), size);
+                let dims = dims_from_point(dim_names, &v, &[a, b], slices);
+
+                v[a] = -size;
+                v[b] = -size;
+                let bottom_left = projection.project(&v);
+
+                v[a] = size;
+                v[b] = -size;
+                let bottom_right = projection.project(&v);
+
+                v[a] = -size;
+                v[b] = size;
+                let top_left = projection.project(&v);
+
+                v[a] = size;
+                v[b] = size;
+                let top_right = projection.project(&v);
+
+                let points = [ bottom_left, bottom_right, top_left, top_right ];
+                faces.push(Face::new(points, [a, b], dims))
+            }
+        }
+    }
+    faces
+}
+
+pub fn edges(dim_names: &[String], size: f32, slices: &[Slice], projection: &impl Project) -> Vec<Edge> {
+    let free = free_dims(dim_names.len(), slices);
+    let dim = free.len() as u32;
+    if dim < 1 {
+        return Vec::new();
+    }
+
+    // This is the same trick as 'faces', but we only pick a single dimension to vary.
+    let mut edges = Vec::with_capacity((dim * 2_u32.pow(dim - 1)) as usize);
+    for d in 0..dim {
+        let a = free[d as usize];
+        for loc in 0..2_u32.pow(dim - 1) {
+            let mut v = point(insert_bit(loc, d), &free, slices, dim_names.len(), size);
+            let dims = dims_from_point(dim_names, &v, &[a], slices);
+
+            v[a] = -size;
+            let start = projection.project(&v);
+
+            v[a] = size;
+            let end = projection.project(&v);
+
+            edges.push(Edge { points: [start, end], varying: a, dims })
+        }
+    }
+    edges
+}
+
+pub fn corners(dim_names: &[String], size: f32, slices: &[Slice], projection: &impl Project) -> Vec<Corner> {
+    let free = free_dims(dim_names.len(), slices);
+    (0..2_u32.pow(free.len() as u32)).map(|bits| {
+        let v = point(bits, &free, slices, dim_names.len(), size);
+        let dims = dims_from_point(dim_names, &v, &[], slices);
+        Corner { point: projection.project(&v), dims }
+    }).collect()
+}
+
+// The dimensions that have been sliced somewhere strictly between their endpoints.
+pub fn sliced(dim_names: &[String], slices: &[Slice]) -> Vec<(String, f32)> {
+    dim_names.iter().enumerate().filter_map(|(ix, nm)| {
+        match slice(slices, ix) {
+            Slice::Fixed(t) if slice(slices, ix).endpoint().is_none() => Some((nm.clone(), t)),
+            _ => None
+        }
+    }).collect()
+}
+
+// The cells of an n-cube that we care about, after projection.
+pub struct Geometry {
+    // The number of dimensions of the goal, including any that have been sliced away.
+    pub dim: usize,
+
+    pub faces: Vec<Face>,
+    // The edges and corners are only pickable when the goal has less than 2 dimensions.
+    pub edges: Vec<Edge>,
+    pub corners: Vec<Corner>,
+
+    // The dimensions that have been sliced somewhere strictly between their endpoints.
+    // These are constraints on every cell, but they don't fit into 'Constraints'.
+    pub sliced: Vec<(String, f32)>,
+
+    // Used to speed up picking, and needs to be rebuilt whenever the faces change.
+    bvh: Bvh
+}
+
+impl Geometry {
+    pub fn new(dim_names: &[String], size: f32, slices: &[Slice], projection: &impl Project) -> Geometry {
+        let faces = faces(dim_names, size, slices, projection);
+        let edges = edges(dim_names, size, slices, projection);
+        let corners = corners(dim_names, size, slices, projection);
+        Geometry::from_cells(dim_names.len(), faces, edges, corners, sliced(dim_names, slices))
+    }
+
+    // Assemble geometry that has been laid out by some other means.
+    pub fn from_cells(dim: usize, faces: Vec<Face>, edges: Vec<Edge>, corners: Vec<Corner>, sliced: Vec<(String, f32)>) -> Geometry {
+        let bvh = Bvh::build(&faces);
+        Geometry {
+            dim,
+            faces,
+            edges,
+            corners,
+            sliced,
+            bvh
+        }
+    }
+
+    // How far the cube reaches from the origin once it has been projected, which is √n for the whole of [-1, 1]^n.
+    pub fn radius(&self) -> f32 {
+        self.corners.iter().map(|corner| corner.point.norm()).fold(0.0, f32::max)
+    }
+
+    // Are we looking at a point or a line?
+    pub fn is_degenerate(&self) -> bool {
+        self.faces.is_empty()
+    }
+
+    // The faces hit by the ray, nearest first.
+    pub fn intersections(&self, origin: Point3<f32>, dir : Vector3<f32>) -> Vec<(Point3<f32>, usize)> {
+        let mut isects = self.bvh.intersections(&self.faces, origin, dir);
+        isects.sort_by_key(|(isect, _)| NotNan::new((origin - isect).norm()).expect("Distance should not be NaN"));
+        isects
+    }
+
+    pub fn pick(&self, origin: Point3<f32>, dir: Vector3<f32>) -> Option<Pick> {
+        if !self.is_degenerate() {
+            return self.intersections(origin, dir).into_iter().next().map(|(_, ix)| Pick::Face(ix));
+        }
+
+        // Corners take priority over edges, as they sit on top of them.
+        let nearest = |isect: &Point3<f32>| NotNan::new((origin - isect).norm()).expect("Distance should not be NaN");
+        let corner = self.corners.iter().enumerate()
+            .filter_map(|(ix, corner)| corner.intersect(origin, dir, PICK_RADIUS).map(|isect| (isect, ix)))
+            .min_by_key(|(isect, _)| nearest(isect));
+        if let Some((_, ix)) = corner {
+            return Some(Pick::Corner(ix));
+        }
+        self.edges.iter().enumerate()
+            .filter_map(|(ix, edge)| edge.intersect(origin, dir, PICK_RADIUS).map(|isect| (isect, ix)))
+            .min_by_key(|(isect, _)| nearest(isect))
+            .map(|(_, ix)| Pick::Edge(ix))
+    }
+
+    // The constraints on the picked cell.
+    pub fn pick_dims(&self, pick: Pick) -> &Constraints {
+        match pick {
+            Pick::Face(ix) => &self.faces[ix].dims,
+            Pick::Edge(ix) => &self.edges[ix].dims,
+            Pick::Corner(ix) => &self.corners[ix].dims
+        }
+    }
+
+    // One edge running along each dimension, coming out of the corner that is as close to 0 in everything else as we can get.
+    // These are the edges we hang the names of the dimensions on.
+    pub fn axis_edges(&self) -> Vec<usize> {
+        let mut axes: Vec<usize> = Vec::new();
+        for (ix, edge) in self.edges.iter().enumerate() {
+            let ones = |edge: &Edge| edge.dims.iter().filter(|(_, d)| *d).count();
+            match axes.iter_mut().find(|axis| self.edges[**axis].varying == edge.varying) {
+                Some(axis) if ones(edge) < ones(&self.edges[*axis]) => *axis = ix,
+                Some(_) => (),
+                None => axes.push(ix)
+            }
+        }
+        axes.sort_by_key(|ix| self.edges[*ix].varying);
+        axes
+    }
+}
+
+// Names for the dimensions in tests, which is most of what they need to say about a cube.
+#[cfg(test)]
+pub mod fixtures {
+    // 'dim' dimensions, called d0, d1 and so on.
+    pub fn dim_names(dim: usize) -> Vec<String> {
+        (0..dim).map(|i| format!("d{}", i)).collect()
+    }
+
+    // For when the names themselves matter to the test.
+    pub fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|nm| nm.to_string()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::fixtures::dim_names;
+    use crate::linalg::{Embedding, Projection};
+
+    fn choose(n: usize, k: usize) -> usize {
+        if k > n {
+            0
+        } else {
+            (0..k).fold(1, |acc, i| acc * (n - i) / (i + 1))
+        }
+    }
+
+    #[test]
+    fn cell_counts() {
+        for dim in 0..=8 {
+            let names = dim_names(dim);
+            let expected_faces = if dim < 2 { 0 } else { choose(dim, 2) * 2_usize.pow(dim as u32 - 2) };
+            let expected_edges = if dim < 1 { 0 } else { dim * 2_usize.pow(dim as u32 - 1) };
+            assert_eq!(faces(&names, 1.0, &[], &Projection::default()).len(), expected_faces, "faces of a {}-cube", dim);
+            assert_eq!(edges(&names, 1.0, &[], &Projection::default()).len(), expected_edges, "edges of a {}-cube", dim);
+            assert_eq!(corners(&names, 1.0, &[], &Projection::default()).len(), 2_usize.pow(dim as u32), "corners of a {}-cube", dim);
+        }
+    }
+
+    #[test]
+    fn cell_constraints() {
+        for dim in 0..=8 {
+            let names = dim_names(dim);
+            assert!(faces(&names, 1.0, &[], &Projection::default()).iter().all(|face| face.dims.len() + 2 == dim));
+            assert!(edges(&names, 1.0, &[], &Projection::default()).iter().all(|edge| edge.dims.len() + 1 == dim));
+            assert!(corners(&names, 1.0, &[], &Projection::default()).iter().all(|corner| corner.dims.len() == dim));
+        }
+    }
+
+    #[test]
+    fn face_constraints() {
+        for dim in 2..=6 {
+            let names = dim_names(dim);
+            let faces = faces(&names, 1.0, &[], &Projection::default());
+            let mut seen = std::collections::HashSet::new();
+            for face in &faces {
+                // Every dimension is either fixed by the face, or varies across it.
+                let mut mentioned: Vec<&String> = face.dims.iter().map(|(nm, _)| nm).collect();
+                mentioned.push(&names[face.varying[0]]);
+                mentioned.push(&names[face.varying[1]]);
+                mentioned.sort();
+                assert_eq!(mentioned, names.iter().collect::<Vec<_>>());
+                assert!(seen.insert((face.varying, face.dims.clone())), "duplicate face {:?}", face);
+
+                // The corner where both varying dimensions are 0 should live where the constraints say it does.
+                let v: Vec<f32> = names.iter().enumerate().map(|(i, nm)| {
+                    match face.dims.iter().find(|(fixed, _)| fixed == nm) {
+                        Some((_, true)) => 1.0,
+                        Some((_, false)) => -1.0,
+                        None => {
+                            assert!(face.varying.contains(&i));
+                            -1.0
+                        }
+                    }
+                }).collect();
+                assert_eq!(face.points[0], Projection::default().project(&v));
+            }
+
+            // Each pair of dimensions gives us 2^(n-2) faces.
+            for d0 in 0..dim {
+                for d1 in d0+1..dim {
+                    let count = faces.iter().filter(|face| face.varying == [d0, d1]).count();
+                    assert_eq!(count, 2_usize.pow(dim as u32 - 2));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn edge_constraints() {
+        let names = dim_names(3);
+        for edge in edges(&names, 1.0, &[], &Projection::default()) {
+            assert!(edge.dims.iter().all(|(nm, _)| *nm != names[edge.varying]));
+            assert_eq!(edge.dims.len(), 2);
+        }
+    }
+
+    #[test]
+    fn radius() {
+        let geometry = Geometry::new(&dim_names(3), 1.0, &[], &Projection::default());
+        assert!((geometry.radius() - 3.0_f32.sqrt()).abs() < 1e-6);
+        // Slicing through the middle of the square leaves a line running across it, and slicing it down to the middle leaves a point.
+        let geometry = Geometry::new(&dim_names(2), 1.0, &[Slice::Free, Slice::Fixed(0.5)], &Projection::default());
+        assert!((geometry.radius() - 1.0).abs() < 1e-6);
+        let geometry = Geometry::new(&dim_names(2), 1.0, &[Slice::Fixed(0.5); 2], &Projection::default());
+        assert_eq!(geometry.radius(), 0.0);
+    }
+
+    #[test]
+    fn pick_face() {
+        let geometry = Geometry::new(&dim_names(3), 1.0, &[], &Projection::default());
+        // Looking straight down the z axis, we should hit the front face first.
+        let origin = Point3::new(0.1, 0.2, 4.0);
+        let pick = geometry.pick(origin, Vector3::new(0.0, 0.0, 1.0)).expect("Should hit the cube");
+        assert_eq!(geometry.pick_dims(pick), &[("d2".to_string(), true)]);
+        assert!(geometry.pick(origin, Vector3::new(0.0, 0.0, -1.0)).is_none());
+    }
+
+    #[test]
+    fn slices() {
+        let names = dim_names(4);
+
+        // Slicing a 4-cube at an endpoint leaves us with a 3-cube, where every cell remembers where the slice was.
+        let slices = [Slice::Free, Slice::Free, Slice::Fixed(1.0)];
+        let geometry = Geometry::new(&names, 1.0, &slices, &Projection::default());
+        assert_eq!(geometry.faces.len(), 6);
+        assert_eq!(geometry.edges.len(), 12);
+        assert_eq!(geometry.corners.len(), 8);
+        assert!(geometry.sliced.is_empty());
+        assert!(geometry.faces.iter().all(|face| face.dims.contains(&("d2".to_string(), true)) && !face.varying.contains(&2)));
+
+        // Slicing through the middle doesn't put the slice on any particular endpoint.
+        let slices = [Slice::Free, Slice::Fixed(0.25), Slice::Free, Slice::Free];
+        let geometry = Geometry::new(&names, 1.0, &slices, &Projection::default());
+        assert_eq!(geometry.faces.len(), 6);
+        assert_eq!(geometry.sliced, vec![("d1".to_string(), 0.25)]);
+        assert!(geometry.faces.iter().all(|face| face.dims.len() == 1 && face.dims[0].0 != "d1"));
+
+        // Once the sliced dimension has been dropped by the embedding, the slice is just an ordinary 3-cube.
+        let embedding = Embedding { slices: slices.to_vec(), ..Embedding::default() };
+        let sliced = Geometry::new(&names, 1.0, &slices, &embedding);
+        let cube = Geometry::new(&dim_names(3), 1.0, &[], &Projection::default());
+        let points = |geometry: &Geometry| geometry.corners.iter().map(|corner| corner.point).collect::<Vec<_>>();
+        assert_eq!(points(&sliced), points(&cube));
+
+        // The axes of the slice come out of the corner that is on the slice.
+        let geometry = Geometry::new(&names, 1.0, &[Slice::Free, Slice::Free, Slice::Fixed(1.0)], &Projection::default());
+        let axes: Vec<&Edge> = geometry.axis_edges().into_iter().map(|ix| &geometry.edges[ix]).collect();
+        assert_eq!(axes.iter().map(|edge| edge.varying).collect::<Vec<_>>(), vec![0, 1, 3]);
+        assert!(axes.iter().all(|edge| edge.dims.iter().all(|(nm, d)| *d == (nm == "d2"))));
+
+        // Slicing everything away leaves a single point.
+        let geometry = Geometry::new(&names, 1.0, &[Slice::Fixed(0.0); 4], &Projection::default());
+        assert!(geometry.is_degenerate());
+        assert_eq!(geometry.corners.len(), 1);
+        assert_eq!(geometry.corners[0].dims.len(), 4);
+    }
+
+    #[test]
+    fn point_goal() {
+        let corners = corners(&[], 1.0, &[], &Projection::default());
+        assert_eq!(corners.len(), 1);
+        assert_eq!(corners[0].point, Vector3::zeros());
+        assert!(corners[0].dims.is_empty());
+
+        // Remember that the picking ray points back towards the viewer.
+        let origin = Point3::new(0.0, 0.0, 4.0);
+        assert!(corners[0].intersect(origin, Vector3::new(0.0, 0.0, 1.0), PICK_RADIUS).is_some());
+        assert!(corners[0].intersect(origin, Vector3::new(0.0, 0.0, -1.0), PICK_RADIUS).is_none());
+        assert!(corners[0].intersect(Point3::new(0.5, 0.0, 4.0), Vector3::new(0.0, 0.0, 1.0), PICK_RADIUS).is_none());
+    }
+
+    #[test]
+    fn line_goal() {
+        let names = dim_names(1);
+        let edges = edges(&names, 1.0, &[], &Projection::default());
+        assert_eq!(edges.len(), 1);
+        assert!(edges[0].dims.is_empty());
+        assert_eq!(edges[0].points, [Vector3::new(-1.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0)]);
+
+        let dir = Vector3::new(0.0, 0.0, 1.0);
+        assert!(edges[0].intersect(Point3::new(0.5, 0.0, 4.0), dir, PICK_RADIUS).is_some());
+        assert!(edges[0].intersect(Point3::new(1.5, 0.0, 4.0), dir, PICK_RADIUS).is_none());
+        assert!(edges[0].intersect(Point3::new(0.5, 0.5, 4.0), dir, PICK_RADIUS).is_none());
+
+        let corners = corners(&names, 1.0, &[], &Projection::default());
+        let endpoints: Vec<_> = corners.iter().map(|corner| corner.dims.clone()).collect();
+        assert_eq!(endpoints, vec![vec![("d0".to_string(), false)], vec![("d0".to_string(), true)]]);
+        assert!(corners[1].intersect(Point3::new(1.0, 0.0, 4.0), dir, PICK_RADIUS).is_some());
+    }
+
+    #[test]
+    fn face_intersection() {
+        let faces = faces(&dim_names(2), 1.0, &[], &Projection::default());
+        assert_eq!(faces.len(), 1);
+        let dir = Vector3::new(0.0, 0.0, 1.0);
+        assert!(faces[0].intersect(Point3::new(0.5, 0.5, 4.0), dir).is_some());
+        assert!(faces[0].intersect(Point3::new(1.5, 0.5, 4.0), dir).is_none());
+    }
+}
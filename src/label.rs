@@ -1,13 +1,21 @@
-use imgui::*;
-
-use nalgebra::{Point3, Vector3, Vector4, Matrix4};
-
-use crate::linalg;
+use crate::abbrev;
+use crate::geometry::Constraints;
+use crate::linalg::Slice;
 use crate::messages;
+use crate::svg;
+use crate::text::TextRenderer;
+use crate::transition;
+
+// Labels stand out against the cube on a pale box, which turns yellow when the label is selected.
+fn background(highlighted: bool, alpha: f32) -> [f32; 4] {
+    if highlighted { [1.0, 0.85, 0.3, alpha] } else { [1.0, 1.0, 1.0, 0.8 * alpha] }
+}
 
 pub struct Label {
     pub position: Vec<f32>,
-    pub txt: String
+    pub txt: String,
+    // Show the whole term, rather than cutting it short?
+    pub expanded: bool
 }
 
 impl Label {
@@ -21,31 +29,263 @@ impl Label {
         }
         Label {
             position,
-            txt: lbl.txt.clone()
+            txt: lbl.txt.clone(),
+            expanded: false
+        }
+    }
+}
+
+// Which endpoint the label is pinned to along each dimension, if any.
+fn pins(position: &[f32]) -> Vec<Option<bool>> {
+    position.iter().map(|pos| if pos.abs() >= 1.0 { Some(*pos > 0.0) } else { None }).collect()
+}
+
+// The labels partway through moving from one goal to the next, along with how faded in they are.
+// Labels are matched up by where they sit on the boundary once the dimensions have been lined up by name, as the terms often change
+// from one goal to the next while the boundary stays put. Where a few labels share a spot, the ones with the same text go together first.
+// The labels that only show up on one side fade in or out where they stand.
+pub fn blend(from_dims: &[String], from: &[Label], to_dims: &[String], to: &[Label], t: f32) -> Vec<(Label, f32)> {
+    let lifted: Vec<Vec<f32>> = from.iter().map(|old| transition::lift(from_dims, to_dims, &old.position)).collect();
+    let mut matches: Vec<Option<usize>> = vec![None; to.len()];
+    let mut used = vec![false; from.len()];
+    for &same_txt in [true, false].iter() {
+        for (lbl, m) in to.iter().zip(matches.iter_mut()).filter(|(_, m)| m.is_none()) {
+            let found = (0..from.len()).find(|ix| {
+                !used[*ix] && pins(&lifted[*ix]) == pins(&lbl.position) && (!same_txt || from[*ix].txt == lbl.txt)
+            });
+            if let Some(ix) = found {
+                used[ix] = true;
+                *m = Some(ix);
+            }
         }
     }
+
+    let mut blended: Vec<(Label, f32)> = to.iter().zip(&matches).map(|(lbl, m)| {
+        match m {
+            Some(ix) => (Label { position: transition::lerp(&lifted[*ix], &lbl.position, t), txt: lbl.txt.clone(), expanded: lbl.expanded }, 1.0),
+            None => (Label { position: lbl.position.clone(), txt: lbl.txt.clone(), expanded: lbl.expanded }, t)
+        }
+    }).collect();
+    for (ix, old) in from.iter().enumerate().filter(|(ix, _)| !used[*ix]) {
+        blended.push((Label { position: lifted[ix].clone(), txt: old.txt.clone(), expanded: old.expanded }, 1.0 - t));
+    }
+    blended
+}
+
+// Labels on the far side of the cube get faded out, so that the near side is easier to read.
+// 'radius' is how far the cube reaches from the origin, so the fade runs from the nearest point it could have to the furthest.
+pub fn depth_fade(distance: f32, eye_distance: f32, radius: f32) -> f32 {
+    if radius <= 0.0 {
+        return 1.0;
+    }
+    let t = ((distance - (eye_distance - radius)) / (2.0 * radius)).clamp(0.0, 1.0);
+    1.0 - 0.7 * t
+}
+
+// The leader line running from 'anchor' out to the nearest point on the box around the label.
+fn leader(anchor: [f32; 2], [min, max]: [[f32; 2]; 2]) -> [[f32; 2]; 2] {
+    [anchor, [anchor[0].clamp(min[0], max[0]), anchor[1].clamp(min[1], max[1])]]
 }
 
 impl Label {
-    pub fn render(&self, mvp: Matrix4<f32>, ui: &Ui) {
-        let projected = linalg::project(&self.position);
-        let window_pos = linalg::window_coords(mvp, ui.io().display_size, projected);
-
-        // We want to truncate the label titles here, as they can get absolutely massive.
-        let title =
-            if self.txt.len() > 10 {
-                format!("{}...##{}\0", self.txt[0..9 - 3].to_owned(), self.txt)
+    // Does the label live on the slice? Labels that aren't pinned to an endpoint of a dimension stretch across all of it.
+    pub fn on_slice(&self, slices: &[Slice], size: f32) -> bool {
+        self.position.iter().zip(slices).all(|(pos, slice)| {
+            match slice.coordinate(size) {
+                Some(coord) if pos.abs() >= size => (pos - coord).abs() < 1e-3,
+                _ => true
+            }
+        })
+    }
+
+    // Is the label pinned to an endpoint of every dimension, and so sitting on a corner of the cube?
+    pub fn at_vertex(&self, size: f32) -> bool {
+        !self.position.is_empty() && self.position.iter().all(|pos| pos.abs() >= size)
+    }
+
+    // Is the label pinned to an endpoint of some dimension, and so on the boundary of the cube?
+    pub fn on_boundary(&self, size: f32) -> bool {
+        self.position.iter().any(|pos| pos.abs() >= size)
+    }
+
+    // Where the label sits, as constraints on the dimensions. Dimensions that the label stretches across are left out,
+    // as are any that we don't have a name for.
+    pub fn constraints(&self, dim_names: &[String], size: f32) -> Vec<String> {
+        self.position.iter().zip(dim_names).filter(|(pos, _)| **pos != 0.0).map(|(pos, nm)| {
+            if pos.abs() >= size {
+                format!("{} = {}", nm, if *pos > 0.0 { 1 } else { 0 })
             } else {
-                format!("{}##{}\0", self.txt, self.txt)
-            };
-
-        let title_imstr = unsafe { ImStr::from_utf8_with_nul_unchecked(title.as_bytes()) };
-        Window::new(title_imstr)
-            .position(window_pos, Condition::Always)
-            .size([100.0, 100.0], Condition::Appearing)
-            .collapsed(true, Condition::Appearing)
-            .build(ui, || {
-                ui.text(&self.txt);
-            });
+                format!("{} = {:.2}", nm, (pos + size) / (2.0 * size))
+            }
+        }).collect()
+    }
+
+    // Does the face lie inside the part of the boundary that the label lives on?
+    // Every dimension the label is pinned to an endpoint of has to be fixed to the same endpoint on the face.
+    pub fn on_face(&self, dim_names: &[String], dims: &Constraints, size: f32) -> bool {
+        self.position.iter().zip(dim_names).all(|(pos, nm)| {
+            pos.abs() < size || dims.iter().any(|(dim, d)| dim == nm && *d == (*pos > 0.0))
+        })
+    }
+
+    // The text we actually show, cut down to 'width' characters. Clicking on a label shows the whole thing.
+    pub fn display_text(&self, width: usize) -> String {
+        if self.expanded {
+            self.txt.clone()
+        } else {
+            abbrev::abbreviate(&self.txt, width)
+        }
+    }
+
+    // Does the label touch the face, either the face itself or somewhere along its edges?
+    // If it does, we also return where on the face it is, which is empty if the label covers the whole face.
+    pub fn on_closed_face(&self, dim_names: &[String], varying: [usize; 2], dims: &Constraints, size: f32) -> Option<Constraints> {
+        let mut edge = Vec::new();
+        for (ix, (pos, nm)) in self.position.iter().zip(dim_names).enumerate() {
+            if pos.abs() < size {
+                continue;
+            }
+            let d = *pos > 0.0;
+            if varying.contains(&ix) {
+                edge.push((nm.clone(), d));
+            } else if !dims.iter().any(|(dim, fixed)| dim == nm && *fixed == d) {
+                return None;
+            }
+        }
+        Some(edge)
+    }
+
+    // Draw the label into the scene with its top left corner at 'pos', tied back to 'anchor' by a leader line.
+    // The leader goes underneath all of the labels, so that it never runs across any text.
+    // Returns the box it takes up on screen, so that it can be clicked on.
+    pub fn render(&self, text: &mut TextRenderer, pos: [f32; 2], anchor: [f32; 2], width: usize, alpha: f32, highlighted: bool) -> [[f32; 2]; 2] {
+        let bounds = text.queue(pos, &self.display_text(width), [0.0, 0.0, 0.0, alpha], background(highlighted, alpha));
+
+        let [from, to] = leader(anchor, bounds);
+        let color = [0.0, 0.0, 0.0, alpha];
+        text.queue_line(from, to, 1.0, color);
+        // Labels on corners get a ring around their corner, so that it's obvious which one they belong to.
+        if self.at_vertex(1.0) {
+            text.queue_circle(anchor, 5.0, color, false);
+        } else {
+            text.queue_circle(anchor, 2.5, color, true);
+        }
+
+        bounds
+    }
+
+    // The same as 'render', but for SVG, where the box around the text has already been measured.
+    pub fn render_svg(&self, drawing: &mut svg::Drawing, bounds: [[f32; 2]; 2], anchor: [f32; 2], width: usize, alpha: f32, highlighted: bool) {
+        let [min, max] = bounds;
+        let [from, to] = leader(anchor, bounds);
+        let color = [0.0, 0.0, 0.0, alpha];
+        drawing.overlay(svg::Shape::Line { from, to, color, width: 1.0 });
+        if self.at_vertex(1.0) {
+            drawing.overlay(svg::Shape::Circle { center: anchor, radius: 5.0, color, filled: false });
+        } else {
+            drawing.overlay(svg::Shape::Circle { center: anchor, radius: 2.5, color, filled: true });
+        }
+        drawing.overlay(svg::Shape::Text { min, max, txt: self.display_text(width), color, background: background(highlighted, alpha) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::fixtures::names;
+
+    fn dims() -> Vec<String> {
+        names(&["i", "j", "k"])
+    }
+
+    fn label(position: &[f32]) -> Label {
+        Label { position: position.to_vec(), txt: "a".to_string(), expanded: false }
+    }
+
+    fn fixed(dims: &[(&str, bool)]) -> Constraints {
+        dims.iter().map(|(nm, d)| (nm.to_string(), *d)).collect()
+    }
+
+    #[test]
+    fn blending() {
+        let from = vec![label(&[1.0, 0.0, 0.0]), Label { txt: "b".to_string(), ..label(&[0.0, -1.0, 0.0]) }, label(&[0.0, -1.0, 0.0])];
+        let to_dims = names(&["j", "i"]);
+        // The label on i = 1 has a new term, but it stays put. Of the two on j = 0, the one with the same text goes with the new one.
+        let to = vec![Label { txt: "c".to_string(), ..label(&[0.0, 1.0]) }, Label { txt: "b".to_string(), ..label(&[-1.0, 0.5]) }];
+        let blended = blend(&dims(), &from, &to_dims, &to, 0.5);
+        let found: Vec<(&str, Vec<f32>, f32)> = blended.iter().map(|(lbl, fade)| (lbl.txt.as_str(), lbl.position.clone(), *fade)).collect();
+        assert_eq!(found, vec![("c", vec![0.0, 1.0], 1.0), ("b", vec![-1.0, 0.25], 1.0), ("a", vec![-1.0, 0.0], 0.5)]);
+    }
+
+    #[test]
+    fn fading() {
+        // A 4-cube reaches out to 2 from the origin, so looking from 5 away, everything is between 3 and 7 away.
+        assert_eq!(depth_fade(3.0, 5.0, 2.0), 1.0);
+        assert!((depth_fade(5.0, 5.0, 2.0) - 0.65).abs() < 1e-6);
+        assert!((depth_fade(7.0, 5.0, 2.0) - 0.3).abs() < 1e-6);
+        // A single point doesn't have a far side.
+        assert_eq!(depth_fade(5.0, 5.0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn leaders() {
+        let bounds = [[10.0, 10.0], [50.0, 30.0]];
+        assert_eq!(leader([0.0, 20.0], bounds), [[0.0, 20.0], [10.0, 20.0]]);
+        assert_eq!(leader([60.0, 40.0], bounds), [[60.0, 40.0], [50.0, 30.0]]);
+        // An anchor underneath the label doesn't need a line at all.
+        assert_eq!(leader([20.0, 20.0], bounds), [[20.0, 20.0], [20.0, 20.0]]);
+    }
+
+    #[test]
+    fn boundary() {
+        assert!(!label(&[0.0, 0.0, 0.0]).on_boundary(1.0));
+        assert!(!label(&[0.5, -0.99, 0.0]).on_boundary(1.0));
+        assert!(label(&[0.0, 1.0, 0.0]).on_boundary(1.0));
+        assert!(label(&[0.0, 0.0, -1.0]).on_boundary(1.0));
+        // The size of the cube decides where the endpoints are.
+        assert!(!label(&[0.0, 1.0, 0.0]).on_boundary(2.0));
+    }
+
+    #[test]
+    fn constraints() {
+        assert!(label(&[0.0, 0.0, 0.0]).constraints(&dims(), 1.0).is_empty());
+        assert_eq!(label(&[0.0, 1.0, -1.0]).constraints(&dims(), 1.0), vec!["j = 1", "k = 0"]);
+        // Labels partway along a dimension say how far along they are.
+        assert_eq!(label(&[-0.5, 0.0, 1.0]).constraints(&dims(), 1.0), vec!["i = 0.25", "k = 1"]);
+        assert_eq!(label(&[1.0, 0.0, 0.0]).constraints(&dims(), 2.0), vec!["i = 0.75"]);
+        // Dimensions without a name get dropped.
+        assert_eq!(label(&[1.0, -1.0, 1.0]).constraints(&dims()[..2], 1.0), vec!["i = 1", "j = 0"]);
+    }
+
+    #[test]
+    fn faces() {
+        let face = fixed(&[("i", true), ("k", false)]);
+        // Labels that aren't pinned anywhere cover every face.
+        assert!(label(&[0.0, 0.0, 0.0]).on_face(&dims(), &face, 1.0));
+        assert!(label(&[0.5, 0.0, -0.5]).on_face(&dims(), &face, 1.0));
+        assert!(label(&[1.0, 0.0, 0.0]).on_face(&dims(), &face, 1.0));
+        assert!(label(&[1.0, 0.0, -1.0]).on_face(&dims(), &face, 1.0));
+        // The wrong endpoint, or a dimension that varies across the face.
+        assert!(!label(&[-1.0, 0.0, 0.0]).on_face(&dims(), &face, 1.0));
+        assert!(!label(&[1.0, 1.0, 0.0]).on_face(&dims(), &face, 1.0));
+    }
+
+    #[test]
+    fn closed_faces() {
+        // The face where i and j vary, and k = 0.
+        let face = |lbl: &Label| lbl.on_closed_face(&dims(), [0, 1], &fixed(&[("k", false)]), 1.0);
+        // In the middle of the face.
+        assert_eq!(face(&label(&[0.0, 0.0, -1.0])), Some(Vec::new()));
+        assert_eq!(face(&label(&[0.3, -0.5, -1.0])), Some(Vec::new()));
+        // Stretching across k, so it covers this face along with the rest.
+        assert_eq!(face(&label(&[0.0, 0.0, 0.0])), Some(Vec::new()));
+        // On the edges where i = 1 and j = 0.
+        assert_eq!(face(&label(&[1.0, 0.0, -1.0])), Some(fixed(&[("i", true)])));
+        assert_eq!(face(&label(&[0.5, -1.0, -1.0])), Some(fixed(&[("j", false)])));
+        // On the corner where i = 0 and j = 1.
+        assert_eq!(face(&label(&[-1.0, 1.0, -1.0])), Some(fixed(&[("i", false), ("j", true)])));
+        // Over on the face where k = 1.
+        assert_eq!(face(&label(&[0.0, 0.0, 1.0])), None);
+        assert_eq!(face(&label(&[1.0, 1.0, 1.0])), None);
     }
 }
@@ -0,0 +1,159 @@
+// Where a label goes relative to its anchor when it has the screen to itself.
+pub const DEFAULT_OFFSET: [f32; 2] = [10.0, 10.0];
+
+// How much room to leave between labels.
+const MARGIN: f32 = 2.0;
+
+// How many rounds of pushing labels apart we do every frame.
+// We start from where the labels were last frame, so this doesn't need to be enough to sort out everything at once.
+const ITERATIONS: usize = 8;
+
+// How far labels drift back towards their anchor every frame, when there is room for them.
+const RETURN_RATE: f32 = 0.2;
+
+// Something that needs a spot on the screen. Items with a higher priority get pushed around less.
+#[derive(Clone, Copy, Debug)]
+pub struct Item {
+    pub anchor: [f32; 2],
+    pub size: [f32; 2],
+    pub priority: f32
+}
+
+// Keeps labels from piling up on top of each other by nudging them apart in screen space.
+// Labels are positioned relative to their anchors, so they follow the anchors around as the camera moves.
+#[derive(Clone, Debug, Default)]
+pub struct Layout {
+    offsets: Vec<[f32; 2]>
+}
+
+fn overlap(items: &[Item], offsets: &[[f32; 2]], i: usize, j: usize) -> [f32; 2] {
+    let min = |k: usize, axis: usize| items[k].anchor[axis] + offsets[k][axis] - MARGIN;
+    let max = |k: usize, axis: usize| items[k].anchor[axis] + offsets[k][axis] + items[k].size[axis] + MARGIN;
+    let along = |axis: usize| (max(i, axis).min(max(j, axis)) - min(i, axis).max(min(j, axis))).max(0.0);
+    [along(0), along(1)]
+}
+
+fn overlaps_any(items: &[Item], offsets: &[[f32; 2]], i: usize) -> bool {
+    (0..items.len()).any(|j| {
+        let [x, y] = overlap(items, offsets, i, j);
+        j != i && x > 0.0 && y > 0.0
+    })
+}
+
+impl Layout {
+    // Work out where the top left corner of every item should go.
+    // The items should be given in the same order every frame, so that we can pick up where we left off.
+    pub fn place(&mut self, items: &[Item]) -> Vec<[f32; 2]> {
+        if self.offsets.len() != items.len() {
+            self.offsets = vec![DEFAULT_OFFSET; items.len()];
+        }
+        let offsets = &mut self.offsets;
+
+        for _ in 0..ITERATIONS {
+            let mut moved = false;
+            for i in 0..items.len() {
+                for j in i+1..items.len() {
+                    let [x, y] = overlap(items, offsets, i, j);
+                    if x <= 0.0 || y <= 0.0 {
+                        continue;
+                    }
+                    moved = true;
+
+                    // Push the boxes apart along whichever axis needs the smaller push,
+                    // splitting it so that the item with the higher priority moves less.
+                    let axis = if x < y { 0 } else { 1 };
+                    let push = if x < y { x } else { y };
+                    let center = |k: usize| items[k].anchor[axis] + offsets[k][axis] + items[k].size[axis] / 2.0;
+                    // Boxes sitting exactly on top of each other get split up by their order, so that the result is stable.
+                    let direction = if center(i) < center(j) || (center(i) == center(j) && i < j) { -1.0 } else { 1.0 };
+                    let total = (items[i].priority + items[j].priority).max(f32::EPSILON);
+                    offsets[i][axis] += direction * push * items[j].priority / total;
+                    offsets[j][axis] -= direction * push * items[i].priority / total;
+                }
+            }
+            if !moved {
+                break;
+            }
+        }
+
+        // Let labels that have been pushed away drift back, as long as they don't run into anything.
+        for i in 0..items.len() {
+            let current = offsets[i];
+            offsets[i] = [
+                current[0] + RETURN_RATE * (DEFAULT_OFFSET[0] - current[0]),
+                current[1] + RETURN_RATE * (DEFAULT_OFFSET[1] - current[1])
+            ];
+            if overlaps_any(items, offsets, i) {
+                offsets[i] = current;
+            }
+        }
+
+        items.iter().zip(offsets.iter()).map(|(item, offset)| [item.anchor[0] + offset[0], item.anchor[1] + offset[1]]).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(x: f32, y: f32, priority: f32) -> Item {
+        Item { anchor: [x, y], size: [60.0, 16.0], priority }
+    }
+
+    fn assert_disjoint(items: &[Item], positions: &[[f32; 2]]) {
+        for i in 0..items.len() {
+            for j in i+1..items.len() {
+                let apart = |axis: usize| {
+                    positions[i][axis] + items[i].size[axis] <= positions[j][axis] + 1e-3
+                        || positions[j][axis] + items[j].size[axis] <= positions[i][axis] + 1e-3
+                };
+                assert!(apart(0) || apart(1), "labels {} and {} overlap at {:?} and {:?}", i, j, positions[i], positions[j]);
+            }
+        }
+    }
+
+    #[test]
+    fn lone_labels_stay_put() {
+        let items = [item(100.0, 100.0, 1.0), item(400.0, 100.0, 1.0)];
+        let positions = Layout::default().place(&items);
+        assert_eq!(positions, vec![[110.0, 110.0], [410.0, 110.0]]);
+    }
+
+    #[test]
+    fn piled_up_labels_get_pulled_apart() {
+        let items: Vec<Item> = (0..6).map(|i| item(200.0 + i as f32, 200.0, 1.0)).collect();
+        let mut layout = Layout::default();
+        // The layout only does a little work every frame, so give it a few frames to settle down.
+        let mut positions = Vec::new();
+        for _ in 0..30 {
+            positions = layout.place(&items);
+        }
+        assert_disjoint(&items, &positions);
+    }
+
+    #[test]
+    fn stable_between_frames() {
+        let items: Vec<Item> = (0..5).map(|i| item(200.0 + 3.0 * i as f32, 200.0 + i as f32, 1.0)).collect();
+        let mut layout = Layout::default();
+        for _ in 0..30 {
+            layout.place(&items);
+        }
+        let before = layout.place(&items);
+
+        // Moving everything along, like a rotating camera does, moves the labels along with it.
+        let moved: Vec<Item> = items.iter().map(|it| Item { anchor: [it.anchor[0] + 5.0, it.anchor[1] - 2.0], ..*it }).collect();
+        let after = layout.place(&moved);
+        for (b, a) in before.iter().zip(&after) {
+            assert!((a[0] - b[0] - 5.0).abs() < 1e-3 && (a[1] - b[1] + 2.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn priority_moves_less() {
+        let items = [item(200.0, 200.0, 1.0), item(200.0, 200.0, 0.1)];
+        let positions = Layout::default().place(&items);
+        let moved = |k: usize| (positions[k][0] - 210.0).abs() + (positions[k][1] - 210.0).abs();
+        assert!(moved(0) < moved(1));
+        assert_disjoint(&items, &positions);
+    }
+}
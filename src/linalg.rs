@@ -1,3 +1,6 @@
+use std::collections::BTreeMap;
+use std::f32::consts::PI;
+
 use nalgebra::*;
 
 fn right_pad_vec<T>(x: &mut Vec<T>, len: usize, pad: T)
@@ -9,20 +12,233 @@ where
     }
 }
 
-pub fn project(v : &[f32]) -> Vector3<f32> {
-    let t : f32 = (45.0_f32.to_radians() / 2.0).tan();
-    let mut tmp = vec![0.0; v.len()];
-    tmp.copy_from_slice(v);
-    right_pad_vec(&mut tmp, 3, 0.0);
+// Anything that can take an n-dimensional point down into 3 dimensions.
+pub trait Project {
+    fn project(&self, v : &[f32]) -> Vector3<f32>;
+}
+
+// The eye distance used by the perspective projection for dimensions we haven't been given a distance for.
+pub const DEFAULT_DISTANCE: f32 = 3.0;
+
+// How we squash an n-dimensional point down into 3 dimensions.
+// Every dimension past the third gets folded into the first three, one at a time, starting with the last.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Projection {
+    // A parallel projection, where each extra dimension is sheared off in its own direction.
+    Orthographic { skew: f32 },
+    // Look at each extra dimension from an eye sitting 'distances[k]' away along it.
+    Perspective { fov: f32, distances: Vec<f32> },
+    // Put the eye just outside the facet where the extra dimension is 1, so that facet frames everything else.
+    Schlegel { distance: f32 },
+    // Draw the 0 side of each extra dimension as a copy of the 1 side, shrunk down by 'inner'.
+    Nested { inner: f32 },
+}
+
+impl Default for Projection {
+    fn default() -> Projection {
+        Projection::Perspective { fov: 45.0, distances: Vec::new() }
+    }
+}
+
+impl Projection {
+    pub const NAMES: [&'static str; 4] = ["Orthographic", "Perspective", "Schlegel", "Nested"];
+
+    // The index of the projection in 'NAMES'.
+    pub fn index(&self) -> usize {
+        match self {
+            Projection::Orthographic { .. } => 0,
+            Projection::Perspective { .. } => 1,
+            Projection::Schlegel { .. } => 2,
+            Projection::Nested { .. } => 3,
+        }
+    }
+
+    // The projection at 'ix' in 'NAMES', with its default parameters.
+    pub fn from_index(ix: usize) -> Projection {
+        match ix {
+            0 => Projection::Orthographic { skew: 0.35 },
+            2 => Projection::Schlegel { distance: 1.5 },
+            3 => Projection::Nested { inner: 0.4 },
+            _ => Projection::default()
+        }
+    }
+
+}
+
+impl Project for Projection {
+    fn project(&self, v : &[f32]) -> Vector3<f32> {
+        let mut tmp = vec![0.0; v.len()];
+        tmp.copy_from_slice(v);
+        right_pad_vec(&mut tmp, 3, 0.0);
+
+        match self {
+            Projection::Orthographic { skew } => {
+                for (k, w) in v.iter().enumerate().skip(3) {
+                    // Spread the directions of the extra dimensions out evenly around the xy-plane,
+                    // tilting them slightly towards z so that they don't line up with any of the faces.
+                    let angle = (k - 3) as f32 * 2.0 * std::f32::consts::PI / (v.len() - 3) as f32 + std::f32::consts::FRAC_PI_4;
+                    tmp[0] += skew * w * angle.cos();
+                    tmp[1] += skew * w * angle.sin();
+                    tmp[2] += skew * w * 0.5;
+                }
+            },
+            Projection::Perspective { fov, distances } => {
+                let t : f32 = (fov.to_radians() / 2.0).tan();
+                for k in (4..=v.len()).rev() {
+                    let distance = distances.get(k - 4).copied().unwrap_or(DEFAULT_DISTANCE);
+                    let proj = tmp[k - 1] + distance;
+                    for p in &mut tmp {
+                        *p = (t * *p) / proj;
+                    }
+                }
+            },
+            Projection::Schlegel { distance } => {
+                // The facet at 1 is left untouched, and everything else shrinks towards the middle.
+                for k in (4..=v.len()).rev() {
+                    let scale = (distance - 1.0) / (distance - tmp[k - 1]);
+                    for p in &mut tmp {
+                        *p *= scale;
+                    }
+                }
+            },
+            Projection::Nested { inner } => {
+                for k in (4..=v.len()).rev() {
+                    let scale = inner + (1.0 - inner) * (tmp[k - 1] + 1.0) / 2.0;
+                    for p in &mut tmp {
+                        *p *= scale;
+                    }
+                }
+            },
+        }
+
+        Vector3::new(tmp[0], tmp[1], tmp[2])
+    }
+}
+
+// A rotation of n-dimensional space, built up out of rotations in the coordinate planes.
+// The planes are always applied in the same order, so the angles completely describe the rotation.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Rotation {
+    // Indexed by the pair of axes '(a, b)' with 'a < b', in radians.
+    angles: BTreeMap<(usize, usize), f32>
+}
+
+impl Rotation {
+    pub fn angle(&self, a: usize, b: usize) -> f32 {
+        if a > b {
+            -self.angle(b, a)
+        } else {
+            self.angles.get(&(a, b)).copied().unwrap_or(0.0)
+        }
+    }
+
+    pub fn set_angle(&mut self, a: usize, b: usize, angle: f32) {
+        if a > b {
+            return self.set_angle(b, a, -angle);
+        }
+        // Keep the angle in (-π, π], so that the sliders don't run out of room.
+        let angle = PI - (PI - angle).rem_euclid(2.0 * PI);
+        if angle == 0.0 {
+            self.angles.remove(&(a, b));
+        } else {
+            self.angles.insert((a, b), angle);
+        }
+    }
+
+    // Rotate by 'delta' radians in the plane spanned by axes 'a' and 'b', taking 'a' towards 'b'.
+    pub fn rotate(&mut self, a: usize, b: usize, delta: f32) {
+        self.set_angle(a, b, self.angle(a, b) + delta);
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.angles.is_empty()
+    }
+
+    // Rotate a point. Planes involving axes that the point doesn't have are ignored.
+    pub fn apply(&self, v: &[f32]) -> Vec<f32> {
+        let mut v = v.to_vec();
+        for (&(a, b), &angle) in &self.angles {
+            if b < v.len() {
+                let (sin, cos) = angle.sin_cos();
+                let (x, y) = (v[a], v[b]);
+                v[a] = cos * x - sin * y;
+                v[b] = sin * x + cos * y;
+            }
+        }
+        v
+    }
+}
+
+// Whether a dimension is free to vary, or has been fixed to a point along the interval.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Slice {
+    Free,
+    // The position along the interval, between 0 and 1.
+    Fixed(f32)
+}
+
+impl Default for Slice {
+    fn default() -> Slice {
+        Slice::Free
+    }
+}
+
+impl Slice {
+    pub fn is_free(&self) -> bool {
+        matches!(self, Slice::Free)
+    }
+
+    // Which end of the interval the slice is at, if any.
+    pub fn endpoint(&self) -> Option<bool> {
+        match self {
+            Slice::Fixed(t) if *t <= 0.0 => Some(false),
+            Slice::Fixed(t) if *t >= 1.0 => Some(true),
+            _ => None
+        }
+    }
+
+    // The coordinate of the slice on a cube that goes from '-size' to 'size'.
+    pub fn coordinate(&self, size: f32) -> Option<f32> {
+        match self {
+            Slice::Free => None,
+            Slice::Fixed(t) => Some(-size + 2.0 * size * t)
+        }
+    }
+}
+
+// Everything that happens to an n-dimensional point on its way down to 3 dimensions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Embedding {
+    // Which dimension of the point ends up on each axis, so 'axes[0]' becomes x, 'axes[1]' becomes y, and so on.
+    // If this doesn't cover every dimension of the point, the dimensions are left in their original order.
+    pub axes: Vec<usize>,
+    // How each of the original dimensions is sliced. Sliced dimensions don't get an axis at all,
+    // so that a slice of an n-cube looks exactly like a lower dimensional cube.
+    pub slices: Vec<Slice>,
+    // The rotation and projection act on the axes, not on the original dimensions.
+    pub rotation: Rotation,
+    pub projection: Projection
+}
+
+impl Embedding {
+    pub fn slice(&self, ix: usize) -> Slice {
+        self.slices.get(ix).copied().unwrap_or_default()
+    }
 
-    for k in (4..=v.len()).rev() {
-        let proj = tmp[k - 1] + 3.0;
-        for p in &mut tmp {
-            *p = (t * *p) / proj;
+    // Move the free dimensions of the point onto their axes, dropping the sliced ones.
+    pub fn permute(&self, v: &[f32]) -> Vec<f32> {
+        if self.axes.len() == v.len() {
+            self.axes.iter().filter(|ix| self.slice(**ix).is_free()).map(|ix| v[*ix]).collect()
+        } else {
+            (0..v.len()).filter(|ix| self.slice(*ix).is_free()).map(|ix| v[ix]).collect()
         }
     }
+}
 
-    Vector3::new(tmp[0], tmp[1], tmp[2])
+impl Project for Embedding {
+    fn project(&self, v : &[f32]) -> Vector3<f32> {
+        self.projection.project(&self.rotation.apply(&self.permute(v)))
+    }
 }
 
 pub fn window_coords(mvp: Matrix4<f32>, screen_dims: [f32; 2], v : Vector3<f32>) -> [f32; 2] {
@@ -40,3 +256,116 @@ pub fn world_coords(proj: Perspective3<f32>, screen_dims:[f32; 2], pos: [f32; 2]
     let ndc_point = Point3::new(-1.0 + 2.0 * (pos[0] / screen_dims[0]), 1.0 - 2.0 * (pos[1] / screen_dims[1]),  1.0);
     proj.unproject_point(&ndc_point)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn projections() -> Vec<Projection> {
+        (0..Projection::NAMES.len()).map(Projection::from_index).collect()
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for (ix, projection) in projections().iter().enumerate() {
+            assert_eq!(projection.index(), ix);
+        }
+    }
+
+    #[test]
+    fn low_dimensions_are_untouched() {
+        for projection in projections() {
+            assert_eq!(projection.project(&[]), Vector3::zeros());
+            assert_eq!(projection.project(&[0.5]), Vector3::new(0.5, 0.0, 0.0));
+            assert_eq!(projection.project(&[0.5, -1.0, 1.0]), Vector3::new(0.5, -1.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn outer_facets() {
+        // Both the Schlegel diagram and the nested cubes leave the 1 side of the extra dimensions alone...
+        for projection in &[Projection::Schlegel { distance: 1.5 }, Projection::Nested { inner: 0.4 }] {
+            assert_eq!(projection.project(&[1.0, -1.0, 1.0, 1.0, 1.0]), Vector3::new(1.0, -1.0, 1.0));
+            // ...and shrink the 0 side.
+            let inner = projection.project(&[1.0, -1.0, 1.0, -1.0]);
+            assert!(inner.norm() < Vector3::new(1.0_f32, -1.0, 1.0).norm());
+        }
+        assert_eq!(Projection::Nested { inner: 0.4 }.project(&[1.0, 1.0, 1.0, -1.0]), Vector3::new(0.4, 0.4, 0.4));
+    }
+
+    #[test]
+    fn rotations() {
+        let mut rotation = Rotation::default();
+        assert!(rotation.is_identity());
+
+        // A quarter turn in the x-w plane swaps the x and w axes around.
+        rotation.rotate(0, 3, PI / 2.0);
+        let v = rotation.apply(&[1.0, 0.5, 0.25, 0.0]);
+        assert!((v[0] - 0.0).abs() < 1e-6 && (v[3] - 1.0).abs() < 1e-6);
+        assert_eq!(&v[1..3], &[0.5, 0.25]);
+        // Points without a w axis are left alone.
+        assert_eq!(rotation.apply(&[1.0, 0.5, 0.25]), vec![1.0, 0.5, 0.25]);
+
+        // Rotating the plane backwards goes the other way.
+        assert!((rotation.angle(3, 0) + PI / 2.0).abs() < 1e-6);
+        rotation.rotate(3, 0, PI / 2.0);
+        assert!(rotation.is_identity());
+
+        // Angles wrap around.
+        rotation.set_angle(1, 2, 3.0 * PI / 2.0);
+        assert!((rotation.angle(1, 2) + PI / 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rotations_preserve_length() {
+        let mut rotation = Rotation::default();
+        rotation.rotate(0, 4, 0.3);
+        rotation.rotate(2, 3, -1.2);
+        rotation.rotate(1, 4, 2.5);
+        let v = [0.1, -0.7, 0.4, 1.0, -1.0];
+        let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm(&rotation.apply(&v)) - norm(&v)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn embedding_axes() {
+        let mut embedding = Embedding::default();
+        let v = [0.1, 0.2, 0.3];
+        assert_eq!(embedding.project(&v), Vector3::new(0.1, 0.2, 0.3));
+
+        embedding.axes = vec![2, 0, 1];
+        assert_eq!(embedding.project(&v), Vector3::new(0.3, 0.1, 0.2));
+        // Axes for the wrong number of dimensions are ignored.
+        assert_eq!(embedding.permute(&[0.1, 0.2]), vec![0.1, 0.2]);
+
+        // The rotation happens after the dimensions have been moved onto their axes.
+        embedding.rotation.rotate(0, 1, PI);
+        let projected = embedding.project(&v);
+        assert!((projected - Vector3::new(-0.3, -0.1, 0.2)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn sliced_axes() {
+        let embedding = Embedding {
+            axes: vec![3, 0, 1, 2],
+            slices: vec![Slice::Free, Slice::Fixed(0.5)],
+            ..Embedding::default()
+        };
+        // The sliced dimension drops out, and everything after it moves up an axis.
+        assert_eq!(embedding.permute(&[0.1, 0.2, 0.3, 0.4]), vec![0.4, 0.1, 0.3]);
+        assert_eq!(Slice::Fixed(0.5).coordinate(1.0), Some(0.0));
+        assert_eq!(Slice::Fixed(1.0).endpoint(), Some(true));
+        assert_eq!(Slice::Fixed(0.5).endpoint(), None);
+        assert_eq!(Slice::Free.endpoint(), None);
+    }
+
+    #[test]
+    fn perspective_distances() {
+        let near = Projection::Perspective { fov: 45.0, distances: vec![1.5] };
+        let far = Projection::Perspective { fov: 45.0, distances: vec![10.0] };
+        let v = [1.0, 1.0, 1.0, 0.0];
+        assert!(near.project(&v).norm() > far.project(&v).norm());
+        // Dimensions without a distance of their own fall back to the default.
+        assert_eq!(Projection::default().project(&v), Projection::Perspective { fov: 45.0, distances: vec![DEFAULT_DISTANCE] }.project(&v));
+    }
+}
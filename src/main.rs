@@ -1,13 +1,40 @@
+mod abbrev;
+mod cli;
 mod linalg;
 mod cube;
+mod geometry;
+mod palette;
+mod bvh;
+mod net;
+mod transition;
 mod label;
+mod layout;
+mod text;
 mod render;
 mod system;
 mod messages;
+mod svg;
+mod tikz;
+mod mesh;
 mod server;
 mod camera;
 mod vertex;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod egl;
 
 fn main() {
-    render::render();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match cli::parse(&args) {
+        Ok(cli::Command::Interactive) => render::render(),
+        Ok(cli::Command::Render { goal, out, size }) => {
+            if let Err(err) = render::render_to_file(&goal, &out, size) {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        },
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
+        }
+    }
 }
@@ -0,0 +1,333 @@
+use std::fmt::Write;
+
+use nalgebra::{Point3, Vector3};
+use serde_json::json;
+
+use crate::geometry::{Constraints, Geometry};
+
+// How thick the edges come out, in the same units as the cube, which runs from -1 to 1.
+pub const TUBE_RADIUS: f32 = 0.015;
+const TUBE_SIDES: usize = 12;
+// Faces that aren't filled in on screen don't have a colour of their own, so they get a see-through grey.
+pub const NEUTRAL_FACE: [f32; 4] = [0.6, 0.6, 0.6, 0.3];
+
+// A solid piece of the cube, ready to be written out for other programs.
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub name: String,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    // Triangles, going counter-clockwise when seen from the outside.
+    pub indices: Vec<u32>,
+    pub color: [f32; 4],
+    // Where the mesh lives on the cube.
+    pub varying: Vec<String>,
+    pub constraints: Constraints
+}
+
+// A spot that doesn't have any geometry of its own, like where a label is.
+#[derive(Clone, Debug)]
+pub struct Marker {
+    pub name: String,
+    pub position: [f32; 3],
+    pub txt: String
+}
+
+// A closed cylinder running from 'from' to 'to'.
+pub fn tube(from: Point3<f32>, to: Point3<f32>, radius: f32) -> Mesh {
+    let axis = to - from;
+    if axis.norm() < f32::EPSILON {
+        return Mesh::default();
+    }
+    let dir = axis.normalize();
+    // Any vector that isn't parallel to the tube will do to build the circle around it.
+    let other = if dir.x.abs() < 0.9 { Vector3::x() } else { Vector3::y() };
+    let u = dir.cross(&other).normalize();
+    let v = dir.cross(&u);
+
+    let mut mesh = Mesh::default();
+    let push = |mesh: &mut Mesh, p: Point3<f32>, n: Vector3<f32>| {
+        mesh.positions.push([p.x, p.y, p.z]);
+        mesh.normals.push([n.x, n.y, n.z]);
+        mesh.positions.len() as u32 - 1
+    };
+    let around = |k: usize| {
+        let angle = 2.0 * std::f32::consts::PI * k as f32 / TUBE_SIDES as f32;
+        u * angle.cos() + v * angle.sin()
+    };
+
+    // The sides get smooth normals, and the ends get their own copies of the vertices so that they stay flat.
+    let sides: Vec<u32> = (0..TUBE_SIDES).flat_map(|k| {
+        let n = around(k);
+        vec![push(&mut mesh, from + radius * n, n), push(&mut mesh, to + radius * n, n)]
+    }).collect();
+    for k in 0..TUBE_SIDES {
+        let next = (k + 1) % TUBE_SIDES;
+        let [bottom, top, next_bottom, next_top] = [sides[2 * k], sides[2 * k + 1], sides[2 * next], sides[2 * next + 1]];
+        mesh.indices.extend_from_slice(&[bottom, next_bottom, top, top, next_bottom, next_top]);
+    }
+    for (end, normal) in [(from, -dir), (to, dir)].iter() {
+        let center = push(&mut mesh, *end, *normal);
+        let ring: Vec<u32> = (0..TUBE_SIDES).map(|k| push(&mut mesh, end + radius * around(k), *normal)).collect();
+        for k in 0..TUBE_SIDES {
+            let (a, b) = (ring[k], ring[(k + 1) % TUBE_SIDES]);
+            // The circle goes counter-clockwise when looking back down the tube from 'to'.
+            if *normal == dir {
+                mesh.indices.extend_from_slice(&[center, a, b]);
+            } else {
+                mesh.indices.extend_from_slice(&[center, b, a]);
+            }
+        }
+    }
+    mesh
+}
+
+// A face of the cube, with its points in the same order as 'geometry::Face'.
+pub fn quad(points: [Point3<f32>; 4]) -> Mesh {
+    let [bottom_left, bottom_right, top_left, _] = points;
+    let normal = (bottom_right - bottom_left).cross(&(top_left - bottom_left)).try_normalize(f32::EPSILON).unwrap_or_else(Vector3::z);
+    Mesh {
+        positions: points.iter().map(|p| [p.x, p.y, p.z]).collect(),
+        normals: vec![[normal.x, normal.y, normal.z]; 4],
+        indices: vec![0, 1, 2, 2, 1, 3],
+        ..Default::default()
+    }
+}
+
+// The whole cube as solid geometry, taken through 'world', with a quad for every face and a tube for every edge that can be seen.
+// The faces are always there, as they're what people usually want to hold on to, but only keep their colours if they're 'filled'.
+pub fn cube(geometry: &Geometry, dim_names: &[String], face_colors: &[[f32; 4]], edge_colors: &[[f32; 4]], filled: bool, world: impl Fn(Vector3<f32>) -> Point3<f32>) -> Vec<Mesh> {
+    let name = |kind: &str, dims: &Constraints| {
+        dims.iter().fold(kind.to_string(), |name, (nm, d)| format!("{} {}={}", name, nm, if *d { 1 } else { 0 }))
+    };
+
+    let mut meshes = Vec::new();
+    for (face, color) in geometry.faces.iter().zip(face_colors) {
+        meshes.push(Mesh {
+            name: name("face", &face.dims),
+            color: if filled { *color } else { NEUTRAL_FACE },
+            varying: face.varying.iter().map(|ix| dim_names[*ix].clone()).collect(),
+            constraints: face.dims.clone(),
+            ..quad(face.points.map(&world))
+        });
+    }
+    for (edge, color) in geometry.edges.iter().zip(edge_colors) {
+        if color[3] <= 0.0 {
+            continue;
+        }
+        meshes.push(Mesh {
+            name: name("edge", &edge.dims),
+            color: *color,
+            varying: vec![dim_names[edge.varying].clone()],
+            constraints: edge.dims.clone(),
+            ..tube(world(edge.points[0]), world(edge.points[1]), TUBE_RADIUS)
+        });
+    }
+    meshes
+}
+
+// Object names in OBJ files run to the end of the line, and Blender doesn't much like spaces in them either.
+fn obj_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<&str>>().join("_")
+}
+
+// Write the meshes out as Wavefront OBJ, along with the MTL file that holds their colours, which should be saved as 'mtl_file'.
+// OBJ has nowhere to put a label, so each one becomes an object with a single point.
+pub fn to_obj(meshes: &[Mesh], markers: &[Marker], mtl_file: &str) -> (String, String) {
+    let mut obj = String::new();
+    let mut mtl = String::new();
+    writeln!(obj, "# Written by coolttviz").unwrap();
+    writeln!(obj, "mtllib {}", mtl_file).unwrap();
+    writeln!(mtl, "# Written by coolttviz").unwrap();
+
+    // OBJ counts vertices from 1, across the whole file.
+    let mut offset = 1;
+    for (ix, mesh) in meshes.iter().enumerate() {
+        writeln!(mtl, "newmtl material{}", ix).unwrap();
+        writeln!(mtl, "Kd {:.4} {:.4} {:.4}", mesh.color[0], mesh.color[1], mesh.color[2]).unwrap();
+        writeln!(mtl, "d {:.4}", mesh.color[3]).unwrap();
+
+        writeln!(obj, "o {}", obj_name(&mesh.name)).unwrap();
+        writeln!(obj, "usemtl material{}", ix).unwrap();
+        for [x, y, z] in &mesh.positions {
+            writeln!(obj, "v {:.6} {:.6} {:.6}", x, y, z).unwrap();
+        }
+        for [x, y, z] in &mesh.normals {
+            writeln!(obj, "vn {:.6} {:.6} {:.6}", x, y, z).unwrap();
+        }
+        for tri in mesh.indices.chunks(3) {
+            let [a, b, c] = [tri[0] + offset, tri[1] + offset, tri[2] + offset];
+            writeln!(obj, "f {}//{} {}//{} {}//{}", a, a, b, b, c, c).unwrap();
+        }
+        offset += mesh.positions.len() as u32;
+    }
+
+    for marker in markers {
+        let [x, y, z] = marker.position;
+        writeln!(obj, "o {}", obj_name(&marker.name)).unwrap();
+        writeln!(obj, "v {:.6} {:.6} {:.6}", x, y, z).unwrap();
+        writeln!(obj, "p -1").unwrap();
+    }
+    (obj, mtl)
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(4 * (bytes.len() + 2) / 3);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        for k in 0..4 {
+            if k <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * k) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// Write the meshes out as a self-contained glTF 2.0 file, with the buffers embedded in it.
+// Every mesh gets its own node, which carries where it lives on the cube in its extras, and the markers become empty nodes.
+pub fn to_gltf(meshes: &[Mesh], markers: &[Marker]) -> String {
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut views = Vec::new();
+    let mut accessors = Vec::new();
+    let mut materials = Vec::new();
+    let mut gltf_meshes = Vec::new();
+    let mut nodes = Vec::new();
+
+    // Everything we put in the buffer is 4 bytes wide, so it all stays aligned.
+    let mut view = |buffer: &mut Vec<u8>, bytes: Vec<u8>, target: u32| {
+        views.push(json!({ "buffer": 0, "byteOffset": buffer.len(), "byteLength": bytes.len(), "target": target }));
+        buffer.extend(bytes);
+        views.len() - 1
+    };
+
+    for mesh in meshes.iter().filter(|mesh| !mesh.indices.is_empty()) {
+        let floats = |vs: &[[f32; 3]]| vs.iter().flatten().flat_map(|f| f.to_le_bytes().to_vec()).collect::<Vec<u8>>();
+        let positions = view(&mut buffer, floats(&mesh.positions), 34962);
+        let normals = view(&mut buffer, floats(&mesh.normals), 34962);
+        let indices = view(&mut buffer, mesh.indices.iter().flat_map(|ix| ix.to_le_bytes().to_vec()).collect(), 34963);
+
+        // Positions have to say how far they go.
+        let min = (0..3).map(|k| mesh.positions.iter().map(|p| p[k]).fold(f32::INFINITY, f32::min)).collect::<Vec<f32>>();
+        let max = (0..3).map(|k| mesh.positions.iter().map(|p| p[k]).fold(f32::NEG_INFINITY, f32::max)).collect::<Vec<f32>>();
+        accessors.push(json!({ "bufferView": positions, "componentType": 5126, "count": mesh.positions.len(), "type": "VEC3", "min": min, "max": max }));
+        accessors.push(json!({ "bufferView": normals, "componentType": 5126, "count": mesh.normals.len(), "type": "VEC3" }));
+        accessors.push(json!({ "bufferView": indices, "componentType": 5125, "count": mesh.indices.len(), "type": "SCALAR" }));
+
+        materials.push(json!({
+            "name": mesh.name,
+            "pbrMetallicRoughness": { "baseColorFactor": mesh.color, "metallicFactor": 0.0, "roughnessFactor": 0.8 },
+            "alphaMode": if mesh.color[3] < 1.0 { "BLEND" } else { "OPAQUE" },
+            "doubleSided": true
+        }));
+        let accessor = accessors.len() - 3;
+        gltf_meshes.push(json!({
+            "name": mesh.name,
+            "primitives": [{ "attributes": { "POSITION": accessor, "NORMAL": accessor + 1 }, "indices": accessor + 2, "material": materials.len() - 1 }]
+        }));
+
+        let constraints: serde_json::Map<String, serde_json::Value> = mesh.constraints.iter().map(|(nm, d)| (nm.clone(), json!(if *d { 1 } else { 0 }))).collect();
+        nodes.push(json!({
+            "name": mesh.name,
+            "mesh": gltf_meshes.len() - 1,
+            "extras": { "varying": mesh.varying, "constraints": constraints }
+        }));
+    }
+
+    for marker in markers {
+        nodes.push(json!({ "name": marker.name, "translation": marker.position, "extras": { "label": marker.txt } }));
+    }
+
+    let gltf = json!({
+        "asset": { "version": "2.0", "generator": "coolttviz" },
+        "scene": 0,
+        "scenes": [{ "nodes": (0..nodes.len()).collect::<Vec<usize>>() }],
+        "nodes": nodes,
+        "meshes": gltf_meshes,
+        "materials": materials,
+        "accessors": accessors,
+        "bufferViews": views,
+        "buffers": [{ "byteLength": buffer.len(), "uri": format!("data:application/octet-stream;base64,{}", base64(&buffer)) }]
+    });
+    serde_json::to_string_pretty(&gltf).expect("glTF should serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::fixtures::names;
+    use crate::linalg::Projection;
+
+    #[test]
+    fn tubes_face_outwards() {
+        let tube = tube(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, 1.0), 0.1);
+        assert_eq!(tube.positions.len(), tube.normals.len());
+        let center = Vector3::new(0.0, 0.0, 0.5);
+        for tri in tube.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| Vector3::from(tube.positions[tri[k] as usize]));
+            let winding = (b - a).cross(&(c - a));
+            assert!(winding.dot(&((a + b + c) / 3.0 - center)) > 0.0, "triangle {:?} faces inwards", tri);
+        }
+    }
+
+    #[test]
+    fn faces_without_filling() {
+        let names = names(&["i", "j", "k"]);
+        let geometry = Geometry::new(&names, 1.0, &[], &Projection::default());
+        let face_colors = vec![[1.0, 0.0, 0.0, 0.15]; geometry.faces.len()];
+        let edge_colors = vec![[0.0, 0.0, 0.0, 1.0]; geometry.edges.len()];
+        let world = |p: Vector3<f32>| Point3::from(p);
+
+        // Faces aren't filled in by default, but they still come along, just without their colours.
+        let meshes = cube(&geometry, &names, &face_colors, &edge_colors, false, world);
+        let faces: Vec<&Mesh> = meshes.iter().filter(|mesh| mesh.name.starts_with("face")).collect();
+        assert_eq!(faces.len(), 6);
+        assert!(faces.iter().all(|face| face.color == NEUTRAL_FACE));
+        assert_eq!(meshes.len(), 6 + 12);
+
+        let meshes = cube(&geometry, &names, &face_colors, &edge_colors, true, world);
+        assert!(meshes.iter().filter(|mesh| mesh.name.starts_with("face")).all(|face| face.color == face_colors[0]));
+    }
+
+    #[test]
+    fn obj_counts_from_one() {
+        let face = quad([Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0), Point3::new(1.0, 1.0, 0.0)]);
+        assert_eq!(face.normals[0], [0.0, 0.0, 1.0]);
+        let meshes = [Mesh { name: "face k=0".to_string(), ..face.clone() }, Mesh { name: "face k=1".to_string(), ..face }];
+        let (obj, mtl) = to_obj(&meshes, &[], "goal.mtl");
+        assert!(obj.contains("mtllib goal.mtl\n"));
+        assert!(obj.contains("o face_k=0\n"));
+        assert!(obj.contains("f 5//5 6//6 7//7\n"));
+        assert!(mtl.contains("newmtl material1\n"));
+    }
+
+    #[test]
+    fn gltf_extras() {
+        let mesh = Mesh {
+            name: "face k=1".to_string(),
+            color: [1.0, 0.0, 0.0, 0.5],
+            varying: vec!["i".to_string(), "j".to_string()],
+            constraints: vec![("k".to_string(), true)],
+            ..quad([Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0), Point3::new(1.0, 1.0, 0.0)])
+        };
+        let marker = Marker { name: "a".to_string(), position: [0.0, 1.0, 0.0], txt: "a".to_string() };
+        let gltf: serde_json::Value = serde_json::from_str(&to_gltf(&[mesh], &[marker])).unwrap();
+        assert_eq!(gltf["nodes"][0]["extras"]["constraints"]["k"], 1);
+        assert_eq!(gltf["nodes"][1]["extras"]["label"], "a");
+        assert_eq!(gltf["materials"][0]["alphaMode"], "BLEND");
+        // 4 positions and 4 normals, at 12 bytes each, and then 6 indices at 4 bytes each.
+        assert_eq!(gltf["buffers"][0]["byteLength"], 4 * 12 * 2 + 6 * 4);
+    }
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+}
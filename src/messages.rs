@@ -2,6 +2,8 @@ use std::collections::HashMap;
 
 use serde::Deserialize;
 
+use crate::linalg::Rotation;
+
 #[derive(Debug, Deserialize)]
 pub struct Label {
     pub position: HashMap<String, f32>,
@@ -15,7 +17,84 @@ pub struct DisplayGoal {
     pub context: String
 }
 
+// Point the camera somewhere in particular. Anything left out stays as it is.
+// Angles are in degrees, and the rotation is given as '[dim, dim, angle]' triples, naming the plane to rotate in.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SetCamera {
+    pub target: Option<[f32; 3]>,
+    pub radius: Option<f32>,
+    pub azimuth: Option<f32>,
+    pub polar: Option<f32>,
+    pub rotation: Option<Vec<(String, String, f32)>>
+}
+
+impl SetCamera {
+    // Turn 'rotation' through the planes that we were asked to, leaving the rest of it alone.
+    // The planes are named by dimension, but the rotation wants them in terms of the axes on screen, which are 'axis_dims'.
+    pub fn rotate(&self, rotation: &Rotation, axis_dims: &[String]) -> Result<Rotation, String> {
+        let mut rotation = rotation.clone();
+        for (a, b, degrees) in self.rotation.iter().flatten() {
+            let slot = |nm: &String| axis_dims.iter().position(|dim| dim == nm);
+            match (slot(a), slot(b)) {
+                (Some(i), Some(j)) if i != j => rotation.set_angle(i, j, degrees.to_radians()),
+                _ => return Err(format!("Can't rotate in the plane between {} and {}, as they aren't two different dimensions on screen", a, b))
+            }
+        }
+        Ok(rotation)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub enum Message {
-    DisplayGoal(DisplayGoal)
+    DisplayGoal(DisplayGoal),
+    SetCamera(SetCamera)
+}
+
+// What we accept from a file when rendering without a window. This is either a bare goal,
+// or the messages that cooltt would have sent us, in the order it would have sent them.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Script {
+    Goal(DisplayGoal),
+    Message(Message),
+    Messages(Vec<Message>)
+}
+
+impl Script {
+    pub fn messages(self) -> Vec<Message> {
+        match self {
+            Script::Goal(goal) => vec![Message::DisplayGoal(goal)],
+            Script::Message(msg) => vec![msg],
+            Script::Messages(msgs) => msgs
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::fixtures::names;
+
+    #[test]
+    fn partial_rotation() {
+        let axis_dims = names(&["i", "j", "k", "l"]);
+        let mut rotation = Rotation::default();
+        rotation.set_angle(0, 3, 0.5);
+        rotation.set_angle(1, 2, 0.25);
+
+        // Only the plane between j and k gets touched, and going from k to j turns the other way.
+        let cam = SetCamera { rotation: Some(vec![("k".to_string(), "j".to_string(), 90.0)]), ..SetCamera::default() };
+        let rotated = cam.rotate(&rotation, &axis_dims).unwrap();
+        assert!((rotated.angle(1, 2) + 90.0_f32.to_radians()).abs() < 1e-6);
+        assert_eq!(rotated.angle(0, 3), 0.5);
+
+        // Leaving the rotation out leaves it as it was.
+        assert_eq!(SetCamera::default().rotate(&rotation, &axis_dims), Ok(rotation.clone()));
+
+        let cam = SetCamera { rotation: Some(vec![("i".to_string(), "m".to_string(), 90.0)]), ..SetCamera::default() };
+        assert!(cam.rotate(&rotation, &axis_dims).is_err());
+        let cam = SetCamera { rotation: Some(vec![("i".to_string(), "i".to_string(), 90.0)]), ..SetCamera::default() };
+        assert!(cam.rotate(&rotation, &axis_dims).is_err());
+    }
 }
@@ -0,0 +1,286 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use nalgebra::Vector3;
+
+use crate::geometry::{self, Constraints, Edge, Face, Geometry};
+use crate::linalg::{Project, Projection, Slice};
+
+// The gap left between separate pieces of the net, in faces.
+const GAP: i32 = 1;
+
+// How big the net is once it has been scaled down to fit in front of the camera.
+const EXTENT: f32 = 3.0;
+
+// A face of the cube, given by the indices of the dimensions that vary across it and the endpoints of the rest.
+type FaceKey = ([usize; 2], Vec<(usize, bool)>);
+
+// An edge of the cube, given by the index of the dimension that varies along it and the endpoints of the rest.
+type EdgeKey = (usize, Vec<(usize, bool)>);
+
+// Where a face lives on the grid. The corner where both varying dimensions are 0 sits at 'origin',
+// and 'axes[k]' is the direction that 'varying[k]' runs in.
+#[derive(Clone, Copy, Debug)]
+struct Placement {
+    origin: [i32; 2],
+    axes: [[i32; 2]; 2]
+}
+
+fn add(p: [i32; 2], q: [i32; 2]) -> [i32; 2] {
+    [p[0] + q[0], p[1] + q[1]]
+}
+
+fn neg(p: [i32; 2]) -> [i32; 2] {
+    [-p[0], -p[1]]
+}
+
+impl Placement {
+    fn corner(&self, s: bool, t: bool) -> [i32; 2] {
+        let mut p = self.origin;
+        if s {
+            p = add(p, self.axes[0]);
+        }
+        if t {
+            p = add(p, self.axes[1]);
+        }
+        p
+    }
+
+    // The grid cell covered by the face, named by its bottom left corner.
+    fn cell(&self) -> [i32; 2] {
+        let corners = [self.corner(false, false), self.corner(true, false), self.corner(false, true), self.corner(true, true)];
+        [corners.iter().map(|p| p[0]).min().unwrap(), corners.iter().map(|p| p[1]).min().unwrap()]
+    }
+}
+
+fn key(dim_names: &[String], face: &Face) -> FaceKey {
+    (face.varying, indexed(dim_names, &face.dims))
+}
+
+fn indexed(dim_names: &[String], dims: &Constraints) -> Vec<(usize, bool)> {
+    let mut fixed: Vec<(usize, bool)> = dims.iter()
+        .map(|(nm, d)| (dim_names.iter().position(|dim| dim == nm).expect("Constraint on an unknown dimension"), *d))
+        .collect();
+    fixed.sort_unstable();
+    fixed
+}
+
+fn named(dim_names: &[String], fixed: &[(usize, bool)]) -> Constraints {
+    fixed.iter().map(|(ix, d)| (dim_names[*ix].clone(), *d)).collect()
+}
+
+// The 2-faces of a (possibly sliced) n-cube, unfolded flat into the z = 0 plane.
+pub struct Net {
+    pub geometry: Geometry,
+    // Edges shared by two faces that are glued together in the net, and so are folds rather than cuts.
+    pub folds: Vec<bool>,
+    // Cut edges that show up more than once in the net get a number, shared between all of their copies.
+    pub seams: Vec<Option<usize>>
+}
+
+// Unfold the cube by walking outwards from a face, laying each neighbour down across the edge it shares with
+// a face we have already placed. Once we can't place anything else without overlapping, we start a new piece
+// off to the side. For a 3-cube this gives the usual cross-shaped net.
+pub fn unfold(dim_names: &[String], size: f32, slices: &[Slice]) -> Net {
+    // We only need the combinatorics of the faces here, so the projection doesn't matter.
+    let faces = geometry::faces(dim_names, size, slices, &Projection::default());
+    let keys: Vec<FaceKey> = faces.iter().map(|face| key(dim_names, face)).collect();
+    let lookup: HashMap<&FaceKey, usize> = keys.iter().enumerate().map(|(ix, key)| (key, ix)).collect();
+    let free: Vec<bool> = (0..dim_names.len()).map(|ix| slices.get(ix).copied().unwrap_or_default().is_free()).collect();
+
+    let mut placements: Vec<Option<Placement>> = vec![None; faces.len()];
+    let mut occupied = HashSet::new();
+    let mut right_edge = -GAP;
+    while let Some(root) = placements.iter().position(|p| p.is_none()) {
+        let placement = Placement { origin: [right_edge + GAP, 0], axes: [[1, 0], [0, 1]] };
+        occupied.insert(placement.cell());
+        placements[root] = Some(placement);
+
+        let mut queue = VecDeque::new();
+        queue.push_back(root);
+        while let Some(ix) = queue.pop_front() {
+            let placement = placements[ix].unwrap();
+            let (varying, fixed) = &keys[ix];
+            right_edge = right_edge.max(placement.cell()[0] + 1);
+
+            // Every edge of the face runs along one of the varying dimensions, on one side of the other.
+            for k in 0..2 {
+                let (a, b) = (varying[k], varying[1 - k]);
+                for &side in &[false, true] {
+                    let start = if side { add(placement.origin, placement.axes[1 - k]) } else { placement.origin };
+                    let outward = if side { placement.axes[1 - k] } else { neg(placement.axes[1 - k]) };
+
+                    // The faces on the other side of the edge vary along 'a' and one of our fixed dimensions.
+                    for (c, gamma) in fixed.iter().filter(|(c, _)| free[*c]) {
+                        let mut neighbour_fixed: Vec<(usize, bool)> = fixed.iter().filter(|(d, _)| d != c).copied().collect();
+                        neighbour_fixed.push((b, side));
+                        neighbour_fixed.sort_unstable();
+                        let neighbour_varying = if a < *c { [a, *c] } else { [*c, a] };
+                        let neighbour = lookup[&(neighbour_varying, neighbour_fixed)];
+                        if placements[neighbour].is_some() {
+                            continue;
+                        }
+
+                        // The neighbour shares our direction along 'a', and 'c' heads away from us.
+                        let (origin, c_axis) = if *gamma { (add(start, outward), neg(outward)) } else { (start, outward) };
+                        let a_axis = placement.axes[k];
+                        let axes = if neighbour_varying[0] == a { [a_axis, c_axis] } else { [c_axis, a_axis] };
+                        let candidate = Placement { origin, axes };
+                        if occupied.insert(candidate.cell()) {
+                            placements[neighbour] = Some(candidate);
+                            queue.push_back(neighbour);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let placements: Vec<Placement> = placements.into_iter().map(|p| p.unwrap()).collect();
+
+    // Scale everything down to a reasonable size, centered on the origin.
+    let cells: Vec<[i32; 2]> = placements.iter().map(|p| p.cell()).collect();
+    let min_x = cells.iter().map(|c| c[0]).min().unwrap_or(0);
+    let max_x = cells.iter().map(|c| c[0] + 1).max().unwrap_or(1);
+    let min_y = cells.iter().map(|c| c[1]).min().unwrap_or(0);
+    let max_y = cells.iter().map(|c| c[1] + 1).max().unwrap_or(1);
+    let scale = EXTENT / (max_x - min_x).max(max_y - min_y) as f32;
+    let center = [(min_x + max_x) as f32 / 2.0, (min_y + max_y) as f32 / 2.0];
+    let to_world = |p: [i32; 2]| Vector3::new((p[0] as f32 - center[0]) * scale, (p[1] as f32 - center[1]) * scale, 0.0);
+
+    let net_faces: Vec<Face> = faces.iter().zip(&placements).map(|(face, placement)| {
+        let points = [
+            to_world(placement.corner(false, false)),
+            to_world(placement.corner(true, false)),
+            to_world(placement.corner(false, true)),
+            to_world(placement.corner(true, true))
+        ];
+        Face::new(points, face.varying, face.dims.clone())
+    }).collect();
+
+    // Collect up the sides of every face, merging the copies of an edge that lie on top of each other.
+    let mut copies: HashMap<(EdgeKey, [[i32; 2]; 2]), usize> = HashMap::new();
+    let mut edges: Vec<(EdgeKey, [[i32; 2]; 2])> = Vec::new();
+    for ((varying, fixed), placement) in keys.iter().zip(&placements) {
+        for k in 0..2 {
+            for &side in &[false, true] {
+                let mut edge_fixed = fixed.clone();
+                edge_fixed.push((varying[1 - k], side));
+                edge_fixed.sort_unstable();
+                let (s, t) = if k == 0 { ((false, side), (true, side)) } else { ((side, false), (side, true)) };
+                let mut ends = [placement.corner(s.0, s.1), placement.corner(t.0, t.1)];
+                ends.sort_unstable();
+                let edge = ((varying[k], edge_fixed), ends);
+                *copies.entry(edge.clone()).or_insert_with(|| {
+                    edges.push(edge);
+                    0
+                }) += 1;
+            }
+        }
+    }
+
+    let mut seam_numbers: HashMap<&EdgeKey, usize> = HashMap::new();
+    let mut placed_copies: HashMap<&EdgeKey, usize> = HashMap::new();
+    for (key, _) in &edges {
+        *placed_copies.entry(key).or_insert(0) += 1;
+    }
+    let folds: Vec<bool> = edges.iter().map(|edge| copies[edge] > 1).collect();
+    let seams: Vec<Option<usize>> = edges.iter().zip(&folds).map(|((key, _), fold)| {
+        if !fold && placed_copies[key] > 1 {
+            let next = seam_numbers.len() + 1;
+            Some(*seam_numbers.entry(key).or_insert(next))
+        } else {
+            None
+        }
+    }).collect();
+
+    let net_edges: Vec<Edge> = edges.iter().map(|((varying, fixed), ends)| {
+        Edge { points: [to_world(ends[0]), to_world(ends[1])], varying: *varying, dims: named(dim_names, fixed) }
+    }).collect();
+
+    Net {
+        geometry: Geometry::from_cells(dim_names.len(), net_faces, net_edges, Vec::new(), geometry::sliced(dim_names, slices)),
+        folds,
+        seams
+    }
+}
+
+// Places n-dimensional points onto an unfolded net, by finding a face that the point lies on.
+pub struct OnNet<'a> {
+    pub geometry: &'a Geometry,
+    pub dim_names: &'a [String],
+    pub size: f32
+}
+
+impl<'a> Project for OnNet<'a> {
+    fn project(&self, v: &[f32]) -> Vector3<f32> {
+        let coord = |nm: &String| self.dim_names.iter().position(|dim| dim == nm).map(|ix| v[ix]).unwrap_or(0.0);
+        // A point that is pinned to an endpoint of a dimension has to be on that side of the face.
+        // If it isn't pinned, then it stretches across the dimension, so any face will do.
+        let agrees = |face: &&Face| face.dims.iter().all(|(nm, d)| {
+            let p = coord(nm);
+            p.abs() < self.size || (p > 0.0) == *d
+        });
+        let pinned = |face: &&Face| face.dims.iter().all(|(nm, _)| coord(nm).abs() >= self.size);
+
+        let face = self.geometry.faces.iter().filter(agrees).find(pinned)
+            .or_else(|| self.geometry.faces.iter().find(agrees));
+        match face {
+            Some(face) => {
+                let [a, b] = face.varying;
+                let s = ((v.get(a).copied().unwrap_or(0.0) + self.size) / (2.0 * self.size)).clamp(0.0, 1.0);
+                let t = ((v.get(b).copied().unwrap_or(0.0) + self.size) / (2.0 * self.size)).clamp(0.0, 1.0);
+                face.points[0] + s * (face.points[1] - face.points[0]) + t * (face.points[2] - face.points[0])
+            },
+            None => Vector3::zeros()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::fixtures::dim_names;
+
+    // Each face of the net should take up its own cell, without overlapping any others.
+    fn assert_no_overlaps(net: &Net) {
+        let mut centers = HashSet::new();
+        for face in &net.geometry.faces {
+            let center = face.points.iter().sum::<Vector3<f32>>() / 4.0;
+            assert!(centers.insert(((center.x * 1000.0).round() as i32, (center.y * 1000.0).round() as i32)));
+            assert!(face.points.iter().all(|p| p.z == 0.0));
+        }
+    }
+
+    #[test]
+    fn three_cube() {
+        let net = unfold(&dim_names(3), 1.0, &[]);
+        assert_eq!(net.geometry.faces.len(), 6);
+        assert_no_overlaps(&net);
+        // A 3-cube unfolds into a single piece, so 5 of its 12 edges get folded and the other 7 get cut in two.
+        assert_eq!(net.folds.iter().filter(|f| **f).count(), 5);
+        assert_eq!(net.geometry.edges.len(), 5 + 2 * 7);
+        let mut seams: Vec<usize> = net.seams.iter().filter_map(|s| *s).collect();
+        seams.sort_unstable();
+        assert_eq!(seams, (1..=7).flat_map(|n| vec![n, n]).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn higher_cubes() {
+        for dim in 2..=6 {
+            let net = unfold(&dim_names(dim), 1.0, &[]);
+            assert_eq!(net.geometry.faces.len(), geometry::faces(&dim_names(dim), 1.0, &[], &Projection::default()).len());
+            assert_no_overlaps(&net);
+        }
+    }
+
+    #[test]
+    fn labels_land_on_their_faces() {
+        let names = dim_names(3);
+        let net = unfold(&names, 1.0, &[]);
+        let on_net = OnNet { geometry: &net.geometry, dim_names: &names, size: 1.0 };
+
+        // A label on d0 = 0 should land in the middle of that face.
+        let face = net.geometry.faces.iter().find(|face| face.dims == vec![("d0".to_string(), false)]).unwrap();
+        let center = face.points.iter().sum::<Vector3<f32>>() / 4.0;
+        assert!((on_net.project(&[-1.0, 0.0, 0.0]) - center).norm() < 1e-5);
+    }
+}
@@ -0,0 +1,156 @@
+// Convert a hue in [0, 1) into a fully saturated RGBA colour.
+fn hue(h: f32, value: f32, alpha: f32) -> [f32; 4] {
+    let h = h.fract() * 6.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    let [r, g, b] = match h as u32 {
+        0 => [1.0, x, 0.0],
+        1 => [x, 1.0, 0.0],
+        2 => [0.0, 1.0, x],
+        3 => [0.0, x, 1.0],
+        4 => [x, 0.0, 1.0],
+        _ => [1.0, 0.0, x]
+    };
+    [r * value, g * value, b * value, alpha]
+}
+
+// The colour of a face, based on which pair of dimensions vary across it.
+// Every pair of an n-cube gets its own evenly spaced hue.
+pub fn face(varying: [usize; 2], dim: usize, alpha: f32) -> [f32; 4] {
+    let [d0, d1] = varying;
+    // The index of the pair '(d0, d1)' when enumerated in the same order as 'geometry::faces'.
+    let pair = d0 * dim - d0 * (d0 + 1) / 2 + (d1 - d0 - 1);
+    let pairs = (dim * (dim - 1) / 2).max(1);
+    hue(pair as f32 / pairs as f32, 0.85, alpha)
+}
+
+// Okabe and Ito's palette, which stays distinguishable under the common forms of colour blindness.
+// We leave out the yellow, as it all but disappears against the white background.
+const OKABE_ITO: [[f32; 3]; 7] = [
+    [0.0, 0.447, 0.698],
+    [0.835, 0.369, 0.0],
+    [0.0, 0.620, 0.451],
+    [0.800, 0.475, 0.655],
+    [0.902, 0.624, 0.0],
+    [0.337, 0.706, 0.914],
+    [0.0, 0.0, 0.0],
+];
+
+// Paul Tol's bright scheme, which is also colourblind safe.
+const TOL_BRIGHT: [[f32; 3]; 7] = [
+    [0.267, 0.467, 0.667],
+    [0.933, 0.400, 0.467],
+    [0.133, 0.533, 0.200],
+    [0.800, 0.733, 0.267],
+    [0.400, 0.800, 0.933],
+    [0.667, 0.200, 0.467],
+    [0.733, 0.733, 0.733],
+];
+
+// How we pick a colour for each dimension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Palette {
+    OkabeIto,
+    TolBright,
+    // Evenly spaced hues. These are easier to tell apart when there are lots of dimensions, but not if you are colour blind.
+    Rainbow,
+    Black
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::OkabeIto
+    }
+}
+
+impl Palette {
+    pub const NAMES: [&'static str; 4] = ["Okabe-Ito", "Tol bright", "Rainbow", "Black"];
+
+    pub fn index(&self) -> usize {
+        match self {
+            Palette::OkabeIto => 0,
+            Palette::TolBright => 1,
+            Palette::Rainbow => 2,
+            Palette::Black => 3
+        }
+    }
+
+    pub fn from_index(ix: usize) -> Palette {
+        match ix {
+            0 => Palette::OkabeIto,
+            1 => Palette::TolBright,
+            2 => Palette::Rainbow,
+            _ => Palette::Black
+        }
+    }
+
+    // The colour of the 'ix'th dimension out of 'dim'. Fixed palettes start repeating once we run out of colours.
+    pub fn dim(&self, ix: usize, dim: usize, alpha: f32) -> [f32; 4] {
+        let fixed = |colors: &[[f32; 3]]| {
+            let [r, g, b] = colors[ix % colors.len()];
+            [r, g, b, alpha]
+        };
+        match self {
+            Palette::OkabeIto => fixed(&OKABE_ITO),
+            Palette::TolBright => fixed(&TOL_BRIGHT),
+            Palette::Rainbow => hue(ix as f32 / dim.max(1) as f32, 0.85, alpha),
+            Palette::Black => [0.0, 0.0, 0.0, alpha]
+        }
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '\''
+}
+
+// Split some text up into runs, marking the ones that are the name of a dimension,
+// so that they can be drawn in the colour of the dimension.
+pub fn highlight<'a>(txt: &'a str, dims: &[String]) -> Vec<(&'a str, Option<usize>)> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    let mut chars = txt.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        let (end, boundary) = match chars.peek() {
+            Some((ix, next)) => (*ix, is_name_char(c) != is_name_char(*next)),
+            None => (txt.len(), true)
+        };
+        if boundary {
+            let run = &txt[start..end];
+            runs.push((run, dims.iter().position(|nm| nm == run)));
+            start = end;
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for (ix, _) in Palette::NAMES.iter().enumerate() {
+            assert_eq!(Palette::from_index(ix).index(), ix);
+        }
+    }
+
+    #[test]
+    fn distinct_colours() {
+        for palette in &[Palette::OkabeIto, Palette::TolBright, Palette::Rainbow] {
+            for dim in 1..=7 {
+                let colors: Vec<[f32; 4]> = (0..dim).map(|ix| palette.dim(ix, dim, 1.0)).collect();
+                for (i, a) in colors.iter().enumerate() {
+                    assert!(colors[i + 1..].iter().all(|b| a != b), "{:?} repeats a colour for {} dimensions", palette, dim);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn highlight_names() {
+        let dims = vec!["i".to_string(), "j'".to_string()];
+        let runs = highlight("A : 𝕀 → i ∨ j' = ij", &dims);
+        let named: Vec<(&str, usize)> = runs.iter().filter_map(|(run, ix)| ix.map(|ix| (*run, ix))).collect();
+        assert_eq!(named, vec![("i", 0), ("j'", 1)]);
+        assert_eq!(runs.iter().map(|(run, _)| *run).collect::<String>(), "A : 𝕀 → i ∨ j' = ij");
+    }
+}
@@ -1,105 +1,1299 @@
+use std::path::Path;
+use std::time::Instant;
+
 use glium::*;
+use glium::backend::Facade;
+use glium::glutin::event::VirtualKeyCode;
 use imgui::*;
 
-use nalgebra::{Perspective3, Unit};
+use nalgebra::{Isometry3, Matrix4, Perspective3, Point3, Unit, Vector3};
 
 use crate::{linalg, system};
 use crate::cube;
+use crate::palette;
+use crate::geometry::{self, Geometry};
+use crate::net;
+use crate::text;
+use crate::transition;
 use crate::camera;
+use crate::abbrev;
 use crate::label;
+use crate::layout;
 use crate::messages;
+use crate::svg;
+use crate::tikz;
+use crate::mesh;
+
+// A saved viewpoint, including how the cube has been turned in the higher dimensions.
+#[derive(Clone, Debug)]
+struct Bookmark {
+    view: camera::View,
+    rotation: linalg::Rotation,
+}
+
+// Display options that should survive the arrival of a new goal.
+#[derive(Clone, Debug)]
+pub struct Options {
+    filled_faces: bool,
+    // Show the faces unfolded flat, rather than projected down into 3D.
+    unfolded: bool,
+    palette: palette::Palette,
+    // The dimensions whose edges we aren't drawing, by name.
+    hidden: Vec<String>,
+    // Mark the corners of the cube?
+    show_vertices: bool,
+    // Write the coordinates of every vertex next to it?
+    vertex_coordinates: bool,
+    // Name the dimensions along the edges of the cube?
+    annotate_axes: bool,
+    // How many characters of a term a label shows.
+    label_width: usize,
+    // Should labels that are off the slice be dimmed, rather than hidden?
+    dim_off_slice: bool,
+    embedding: linalg::Embedding,
+    // The dimension that modifier+drag rotates the screen axes into.
+    rotation_dim: usize,
+    // Ctrl+digit saves a bookmark, and the digit on its own brings it back.
+    bookmarks: [Option<Bookmark>; 10],
+    // Where exports get written, minus the extension.
+    export_name: ImString,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            filled_faces: false,
+            unfolded: false,
+            palette: palette::Palette::default(),
+            hidden: Vec::new(),
+            show_vertices: false,
+            vertex_coordinates: false,
+            annotate_axes: true,
+            label_width: abbrev::DEFAULT_WIDTH,
+            dim_off_slice: true,
+            embedding: linalg::Embedding::default(),
+            rotation_dim: 3,
+            bookmarks: Default::default(),
+            export_name: ImString::new("goal"),
+        }
+    }
+}
+
+// What the scene looked like before the latest goal arrived, so that we can animate from it.
+struct Transition {
+    started: Instant,
+    dims: Vec<String>,
+    embedding: linalg::Embedding,
+    labels: Vec<label::Label>,
+}
+
+// Which labels show up in the label panel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LabelFilter {
+    All,
+    HoveredFace,
+    Boundary,
+}
+
+impl LabelFilter {
+    const NAMES: [&'static str; 3] = ["All", "On the hovered face", "On the boundary"];
+
+    fn index(&self) -> usize {
+        match self {
+            LabelFilter::All => 0,
+            LabelFilter::HoveredFace => 1,
+            LabelFilter::Boundary => 2,
+        }
+    }
+
+    fn from_index(ix: usize) -> LabelFilter {
+        match ix {
+            1 => LabelFilter::HoveredFace,
+            2 => LabelFilter::Boundary,
+            _ => LabelFilter::All,
+        }
+    }
+}
 
 pub struct Scene {
     camera: camera::Camera,
 
+    options: Options,
+
     cube: cube::Cube,
+    // The numbers marking matching cut edges of the net, if we are showing one.
+    seams: Vec<Option<usize>>,
+    // The face picked out in the face table, if any.
+    selected_face: Option<usize>,
+
+    transition: Option<Transition>,
 
     program: glium::Program,
 
     dims: Vec<String>,
     labels: Vec<label::Label>,
+    text: text::TextRenderer,
+    layout: layout::Layout,
+    label_search: ImString,
+    label_filter: LabelFilter,
+    selected_label: Option<usize>,
+    // The last face the mouse went over. This sticks around when the mouse leaves the cube, so that we can filter labels by it.
+    hovered_face: Option<usize>,
     context: String,
+    // How the last export went.
+    export_status: Option<String>,
+    // What was wrong with the last message from cooltt, if anything.
+    message_error: Option<String>,
 }
 
-fn init_scene(display: &glium::Display, msg: &messages::DisplayGoal) -> Scene {
+fn init_scene<F: Facade>(display: &F, msg: &messages::DisplayGoal, mut options: Options) -> Scene {
     let camera = camera::Camera::new();
 
+    if options.embedding.axes.len() != msg.dims.len() {
+        options.embedding.axes = (0..msg.dims.len()).collect();
+    }
+    options.embedding.slices.resize(msg.dims.len(), linalg::Slice::Free);
+
     let program = program!(display, 140 => {
         vertex: include_str!("../resources/shader.vert"),
         fragment: include_str!("../resources/shader.frag")
     }).unwrap();
 
     let labels = msg.labels.iter().map(|lbl| label::Label::new(&msg.dims, lbl)).collect();
-    let cube = cube::Cube::new(display, &msg.dims, 1.0);
+    let (cube, seams) = build_cube(display, &msg.dims, &options);
 
     Scene {
         camera,
+        options,
         program,
         cube,
+        seams,
+        selected_face: None,
+        transition: None,
         labels,
         dims: msg.dims.clone(),
+        text: text::TextRenderer::new(display),
+        layout: layout::Layout::default(),
+        label_search: ImString::with_capacity(64),
+        label_filter: LabelFilter::All,
+        selected_label: None,
+        hovered_face: None,
         context: msg.context.clone(),
+        export_status: None,
+        message_error: None,
     }
 }
 
-fn render_frame(ui: &Ui, scene : &mut Scene, target: &mut Frame) {
+// Points and lines don't have any faces to unfold, so we stick to the 3D view for those.
+fn build_cube<F: Facade>(display: &F, dims: &[String], options: &Options) -> (cube::Cube, Vec<Option<usize>>) {
+    let embedding = &options.embedding;
+    let free = embedding.slices.iter().filter(|slice| slice.is_free()).count();
+    if options.unfolded && free >= 2 {
+        let net = net::unfold(dims, 1.0, &embedding.slices);
+        let mut cube = cube::Cube::new(display, net.geometry);
+        cube.set_edge_colors(&edge_colors(&cube.geometry, dims, options, &net.folds));
+        (cube, net.seams)
+    } else {
+        let mut cube = cube::Cube::new(display, Geometry::new(dims, 1.0, &embedding.slices, embedding));
+        cube.set_edge_colors(&edge_colors(&cube.geometry, dims, options, &[]));
+        (cube, Vec::new())
+    }
+}
+
+// Every edge gets the colour of the dimension that varies along it.
+// Folded edges of a net are drawn faintly, so that the cuts stand out, and hidden dimensions aren't drawn at all.
+fn edge_colors(geometry: &Geometry, dims: &[String], options: &Options, folds: &[bool]) -> Vec<[f32; 4]> {
+    geometry.edges.iter().enumerate().map(|(ix, edge)| {
+        let alpha =
+            if options.hidden.contains(&dims[edge.varying]) {
+                0.0
+            } else if folds.get(ix).copied().unwrap_or(false) {
+                0.35
+            } else {
+                1.0
+            };
+        options.palette.dim(edge.varying, dims.len(), alpha)
+    }).collect()
+}
+
+// Carry the order of the axes over to a new goal, by matching up the names of the dimensions.
+// Any new dimensions get tacked onto the end, in the order the goal gives them.
+fn match_axes(old_dims: &[String], old_axes: &[usize], new_dims: &[String]) -> Vec<usize> {
+    let mut axes: Vec<usize> = old_axes.iter()
+        .filter_map(|ix| new_dims.iter().position(|nm| *nm == old_dims[*ix]))
+        .collect();
+    for ix in 0..new_dims.len() {
+        if !axes.contains(&ix) {
+            axes.push(ix);
+        }
+    }
+    axes
+}
+
+// The vertical field of view of the camera, in degrees.
+const FOV: f32 = 45.0;
+
+fn perspective([width, height]: [f32; 2]) -> Perspective3<f32> {
+    Perspective3::new(width / height, FOV.to_radians(), 0.1, 100.0)
+}
+
+// How many frames it takes for the labels to get out of each other's way, when we only get to show the result.
+const SETTLING_FRAMES: usize = 30;
+
+const AXIS_NAMES: [&str; 3] = ["x", "y", "z"];
+
+fn axis_name(slot: usize) -> String {
+    match AXIS_NAMES.get(slot) {
+        Some(nm) => nm.to_string(),
+        None => format!("w{}", slot - 2)
+    }
+}
+
+impl Scene {
+    // The names of the dimensions, in the order that they are assigned to axes.
+    // Sliced dimensions don't get an axis.
+    fn axis_dims(&self) -> Vec<String> {
+        let embedding = &self.options.embedding;
+        embedding.axes.iter().filter(|ix| embedding.slice(**ix).is_free()).map(|ix| self.dims[*ix].clone()).collect()
+    }
+
+    // Back the camera off so that the whole goal fits on screen.
+    fn fit_view(&mut self) {
+        let geometry = &self.cube.geometry;
+        let points: Vec<Vector3<f32>> = geometry.edges.iter().flat_map(|edge| edge.points.to_vec())
+            .chain(geometry.corners.iter().map(|corner| corner.point))
+            .map(|p| self.cube.model.transform_point(&Point3::from(p)).coords)
+            .collect();
+        let view = self.camera.fit(&points, FOV.to_radians());
+        self.camera.animate_to(view);
+    }
+
+    fn save_bookmark(&mut self, ix: usize) {
+        self.options.bookmarks[ix] = Some(Bookmark {
+            view: self.camera.current(),
+            rotation: self.options.embedding.rotation.clone(),
+        });
+    }
+
+    fn restore_bookmark<F: Facade>(&mut self, ix: usize, display: &F) {
+        if let Some(bookmark) = self.options.bookmarks[ix].clone() {
+            self.camera.animate_to(bookmark.view);
+            self.options.embedding.rotation = bookmark.rotation;
+            self.rebuild(display);
+        }
+    }
+
+    // Where a label ends up in the scene, in world coordinates.
+    fn label_point(&self, ix: usize) -> Point3<f32> {
+        let position = &self.labels[ix].position;
+        let projected =
+            if self.seams.is_empty() {
+                linalg::Project::project(&self.options.embedding, position)
+            } else {
+                linalg::Project::project(&net::OnNet { geometry: &self.cube.geometry, dim_names: &self.dims, size: 1.0 }, position)
+            };
+        self.cube.model.transform_point(&Point3::from(projected))
+    }
+
+    // The geometry is all baked in at projection time, so we need to rebuild the cube whenever the embedding changes.
+    fn rebuild<F: Facade>(&mut self, display: &F) {
+        // The faces get renumbered when the slices change, so we find the selected ones again by where they sit on the cube.
+        let key = |face: &geometry::Face| (face.varying, face.dims.clone());
+        let faces = &self.cube.geometry.faces;
+        let selected = self.selected_face.and_then(|ix| faces.get(ix)).map(key);
+        let hovered = self.hovered_face.and_then(|ix| faces.get(ix)).map(key);
+
+        let (cube, seams) = build_cube(display, &self.dims, &self.options);
+        self.cube = cube;
+        self.seams = seams;
+        let faces = &self.cube.geometry.faces;
+        let find = |k: ([usize; 2], geometry::Constraints)| faces.iter().position(|face| key(face) == k);
+        self.selected_face = selected.and_then(find);
+        self.hovered_face = hovered.and_then(find);
+    }
+
+    // Move the cube along towards the new goal, returning how far through the transition we are.
+    fn animate<F: Facade>(&mut self, display: &F) -> Option<f32> {
+        let t = self.transition.as_ref()?.started.elapsed().as_secs_f32() / transition::DURATION;
+        if t >= 1.0 {
+            self.transition = None;
+            self.rebuild(display);
+            return None;
+        }
+
+        let old = self.transition.as_ref()?;
+        let morph = transition::Morph { from: &old.embedding, from_dims: &old.dims, to: &self.options.embedding, to_dims: &self.dims, t };
+        let geometry = Geometry::new(&self.dims, 1.0, &self.options.embedding.slices, &morph);
+        // The cells only change if the cube gets rebuilt partway through, say by slicing it, so we can usually keep the buffers we have.
+        if self.cube.fits(&geometry) {
+            self.cube.set_geometry(geometry);
+        } else {
+            self.cube = cube::Cube::new(display, geometry);
+            self.cube.set_edge_colors(&edge_colors(&self.cube.geometry, &self.dims, &self.options, &[]));
+        }
+        Some(t)
+    }
+}
+
+// Returns true if the projection has changed.
+fn projection_ui(ui: &Ui, projection: &mut linalg::Projection, dims: &[String]) -> bool {
+    let mut changed = false;
+
+    let names: Vec<ImString> = linalg::Projection::NAMES.iter().map(|nm| ImString::new(*nm)).collect();
+    let names: Vec<&ImStr> = names.iter().map(|nm| nm.as_ref()).collect();
+    let mut ix = projection.index();
+    if ComboBox::new(im_str!("Projection")).build_simple_string(ui, &mut ix, &names) {
+        *projection = linalg::Projection::from_index(ix);
+        changed = true;
+    }
+
+    match projection {
+        linalg::Projection::Orthographic { skew } => {
+            changed |= Slider::new(im_str!("Skew")).range(0.0..=1.0).build(ui, skew);
+        },
+        linalg::Projection::Perspective { fov, distances } => {
+            changed |= Slider::new(im_str!("FOV")).range(10.0..=120.0).build(ui, fov);
+            // Every dimension past the third gets its own eye.
+            for (k, nm) in dims.iter().enumerate().skip(3) {
+                if distances.len() < k - 2 {
+                    distances.resize(k - 2, linalg::DEFAULT_DISTANCE);
+                }
+                changed |= Slider::new(&im_str!("Distance ({})", nm)).range(1.1..=10.0).build(ui, &mut distances[k - 3]);
+            }
+        },
+        linalg::Projection::Schlegel { distance } => {
+            changed |= Slider::new(im_str!("Distance")).range(1.05..=5.0).build(ui, distance);
+        },
+        linalg::Projection::Nested { inner } => {
+            changed |= Slider::new(im_str!("Inner scale")).range(0.05..=0.95).build(ui, inner);
+        },
+    }
+
+    changed
+}
+
+// Returns true if the rotation has changed.
+fn rotation_ui(ui: &Ui, rotation: &mut linalg::Rotation, rotation_dim: &mut usize, dims: &[String]) -> bool {
+    let mut changed = false;
+
+    if dims.len() > 3 {
+        let names: Vec<ImString> = dims[3..].iter().map(|nm| ImString::new(nm.as_str())).collect();
+        let names: Vec<&ImStr> = names.iter().map(|nm| nm.as_ref()).collect();
+        let mut ix = rotation_dim.saturating_sub(3).min(names.len() - 1);
+        ComboBox::new(im_str!("Shift/Ctrl+drag into")).build_simple_string(ui, &mut ix, &names);
+        *rotation_dim = ix + 3;
+    }
+
+    for a in 0..dims.len() {
+        for b in a+1..dims.len() {
+            let mut degrees = rotation.angle(a, b).to_degrees();
+            if Slider::new(&im_str!("{}–{}", dims[a], dims[b])).range(-180.0..=180.0).build(ui, &mut degrees) {
+                rotation.set_angle(a, b, degrees.to_radians());
+                changed = true;
+            }
+        }
+    }
+
+    if ui.small_button(im_str!("Reset rotation")) {
+        *rotation = linalg::Rotation::default();
+        changed = true;
+    }
+
+    changed
+}
+
+fn camera_ui<F: Facade>(ui: &Ui, display: &F, scene: &mut Scene) {
+    let mut arcball = scene.camera.arcball();
+    if ui.checkbox(im_str!("Arcball"), &mut arcball) {
+        scene.camera.set_arcball(arcball);
+    }
+    ui.text_disabled("Right or middle drag to pan");
+    for (ix, nm) in camera::PRESETS.iter().enumerate() {
+        if ix > 0 {
+            ui.same_line_with_spacing(0.0, -1.0);
+        }
+        if ui.small_button(&im_str!("{} ({:?})", nm, PRESET_KEYS[ix])) {
+            let view = scene.camera.preset(ix);
+            scene.camera.animate_to(view);
+        }
+    }
+    if ui.small_button(im_str!("Fit to view (Home)")) {
+        scene.fit_view();
+    }
+
+    ui.text_disabled("Ctrl+digit saves a view, digit restores it");
+    let mut restore = None;
+    for (ix, bookmark) in scene.options.bookmarks.iter().enumerate() {
+        if bookmark.is_some() {
+            if ui.small_button(&im_str!("Restore {}", (ix + 1) % 10)) {
+                restore = Some(ix);
+            }
+            ui.same_line_with_spacing(0.0, -1.0);
+        }
+    }
+    ui.new_line();
+    if let Some(ix) = restore {
+        scene.restore_bookmark(ix, display);
+    }
+}
+
+// Returns true if the axes have been reordered.
+fn axes_ui(ui: &Ui, axes: &mut Vec<usize>, slices: &[linalg::Slice], dims: &[String]) -> bool {
+    ui.text_disabled("Drag to reassign axes");
+    let mut moved = None;
+    let mut axis = 0;
+    for (slot, dim) in axes.iter().enumerate() {
+        // Sliced dimensions keep their place in the order, but don't take up an axis.
+        let label = if slices[*dim].is_free() {
+            axis += 1;
+            im_str!("{}: {}##axis{}", axis_name(axis - 1), dims[*dim], slot)
+        } else {
+            im_str!("-: {} (sliced)##axis{}", dims[*dim], slot)
+        };
+        Selectable::new(&label).build(ui);
+        if let Some(tooltip) = DragDropSource::new(im_str!("AXIS")).begin_payload(ui, slot) {
+            ui.text(format!("Move {}", dims[*dim]));
+            tooltip.end();
+        }
+        if let Some(target) = DragDropTarget::new(ui) {
+            if let Some(Ok(payload)) = target.accept_payload::<usize>(im_str!("AXIS"), DragDropFlags::empty()) {
+                moved = Some((payload.data, slot));
+            }
+            target.pop();
+        }
+    }
+
+    match moved {
+        Some((from, to)) if from != to => {
+            let dim = axes.remove(from);
+            axes.insert(to, dim);
+            true
+        },
+        _ => false
+    }
+}
+
+// Returns true if any of the slices have changed.
+fn slices_ui(ui: &Ui, slices: &mut [linalg::Slice], dims: &[String]) -> bool {
+    let mut changed = false;
+    for (ix, nm) in dims.iter().enumerate() {
+        let mut fixed = !slices[ix].is_free();
+        if ui.checkbox(&im_str!("Fix {}", nm), &mut fixed) {
+            slices[ix] = if fixed { linalg::Slice::Fixed(0.0) } else { linalg::Slice::Free };
+            changed = true;
+        }
+        if let linalg::Slice::Fixed(t) = &mut slices[ix] {
+            changed |= Slider::new(&im_str!("##slice{}", ix)).range(0.0..=1.0).build(ui, t);
+            ui.same_line_with_spacing(0.0, -1.0);
+            if ui.small_button(&im_str!("0##slice{}", ix)) {
+                *t = 0.0;
+                changed = true;
+            }
+            ui.same_line_with_spacing(0.0, -1.0);
+            if ui.small_button(&im_str!("1##slice{}", ix)) {
+                *t = 1.0;
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+// Draw an arrow between two points in window coordinates.
+fn arrow(draw_list: &DrawListMut, from: [f32; 2], to: [f32; 2], color: [f32; 4]) {
+    let (dx, dy) = (to[0] - from[0], to[1] - from[1]);
+    let len = (dx * dx + dy * dy).sqrt();
+    // Edges that point straight at the camera don't have a sensible direction on screen.
+    if len < 1.0 {
+        return;
+    }
+    let (ux, uy) = (dx / len, dy / len);
+    let head = 8.0_f32.min(len / 2.0);
+    draw_list.add_line(from, to, color).thickness(1.5).build();
+    draw_list.add_triangle(
+        to,
+        [to[0] - head * ux - head * uy / 2.0, to[1] - head * uy + head * ux / 2.0],
+        [to[0] - head * ux + head * uy / 2.0, to[1] - head * uy - head * ux / 2.0],
+        color
+    ).filled(true).build();
+}
+
+// Label an edge along each dimension with the name of the dimension, with an arrow pointing from 0 to 1.
+// We also draw a little gizmo in the corner showing which way each dimension currently points.
+fn annotate_axes(ui: &Ui, scene: &Scene, mvp: Matrix4<f32>, view: Isometry3<f32>) {
     let [width, height] = ui.io().display_size;
+    let draw_list = ui.get_background_draw_list();
+    let geometry = &scene.cube.geometry;
+
+    let color = |ix: usize| scene.options.palette.dim(ix, scene.dims.len(), 1.0);
+    let axes: Vec<usize> = geometry.axis_edges().into_iter().filter(|ix| !scene.options.hidden.contains(&scene.dims[geometry.edges[*ix].varying])).collect();
+
+    for ix in &axes {
+        let edge = &geometry.edges[*ix];
+        let from = linalg::window_coords(mvp, ui.io().display_size, edge.points[0]);
+        let to = linalg::window_coords(mvp, ui.io().display_size, edge.points[1]);
+        arrow(&draw_list, from, to, color(edge.varying));
+        let mid = [(from[0] + to[0]) / 2.0 + 6.0, (from[1] + to[1]) / 2.0 + 6.0];
+        draw_list.add_text(mid, color(edge.varying), &scene.dims[edge.varying]);
+    }
+
+    let radius = 40.0;
+    let center = [width - radius - 20.0, height - radius - 20.0];
+    draw_list.add_circle(center, radius + 10.0, [1.0, 1.0, 1.0, 0.8]).filled(true).build();
+    let rotation = view.rotation * scene.cube.model.isometry.rotation;
+    for ix in &axes {
+        let edge = &geometry.edges[*ix];
+        let dir = edge.points[1] - edge.points[0];
+        if dir.norm() < 1e-6 {
+            continue;
+        }
+        let dir: Vector3<f32> = rotation * dir.normalize();
+        let tip = [center[0] + radius * dir.x, center[1] - radius * dir.y];
+        arrow(&draw_list, center, tip, color(edge.varying));
+        draw_list.add_text([tip[0] + 2.0, tip[1] - 14.0], color(edge.varying), &scene.dims[edge.varying]);
+    }
+}
+
+// A line of text, with the names of dimensions picked out in their colours.
+fn colored_text(ui: &Ui, scene: &Scene, line: &str) {
+    let runs = palette::highlight(line, &scene.dims);
+    if runs.is_empty() {
+        ui.text("");
+    }
+    for (ix, (run, dim)) in runs.iter().enumerate() {
+        if ix > 0 {
+            ui.same_line_with_spacing(0.0, 0.0);
+        }
+        match dim {
+            Some(dim) => ui.text_colored(scene.options.palette.dim(*dim, scene.dims.len(), 1.0), run),
+            None => ui.text(run)
+        }
+    }
+}
+
+// Returns true if the edge colours need to change.
+fn legend_ui(ui: &Ui, options: &mut Options, dims: &[String]) -> bool {
+    let mut changed = false;
+
+    let names: Vec<ImString> = palette::Palette::NAMES.iter().map(|nm| ImString::new(*nm)).collect();
+    let names: Vec<&ImStr> = names.iter().map(|nm| nm.as_ref()).collect();
+    let mut ix = options.palette.index();
+    if ComboBox::new(im_str!("Palette")).build_simple_string(ui, &mut ix, &names) {
+        options.palette = palette::Palette::from_index(ix);
+        changed = true;
+    }
+
+    for (ix, nm) in dims.iter().enumerate() {
+        let mut visible = !options.hidden.contains(nm);
+        if ui.checkbox(&im_str!("##legend{}", ix), &mut visible) {
+            if visible {
+                options.hidden.retain(|hidden| hidden != nm);
+            } else {
+                options.hidden.push(nm.clone());
+            }
+            changed = true;
+        }
+        ui.same_line_with_spacing(0.0, -1.0);
+        ui.text_colored(options.palette.dim(ix, dims.len(), 1.0), nm);
+    }
+
+    changed
+}
+
+// Every label, with a search box and some filters, for when there are too many to find by eye.
+fn labels_ui(ui: &Ui, scene: &mut Scene) {
+    InputText::new(ui, im_str!("Search"), &mut scene.label_search).resize_buffer(true).build();
+    let names: Vec<ImString> = LabelFilter::NAMES.iter().map(|nm| ImString::new(*nm)).collect();
+    let names: Vec<&ImStr> = names.iter().map(|nm| nm.as_ref()).collect();
+    let mut ix = scene.label_filter.index();
+    if ComboBox::new(im_str!("Show")).build_simple_string(ui, &mut ix, &names) {
+        scene.label_filter = LabelFilter::from_index(ix);
+    }
+    if scene.label_filter == LabelFilter::HoveredFace && scene.hovered_face.is_none() {
+        ui.text_disabled("Hover over a face to see its labels");
+    }
+    ui.separator();
+
+    let search = scene.label_search.to_str().to_lowercase();
+    let mut selected = None;
+    for (ix, lbl) in scene.labels.iter().enumerate() {
+        let shown = match scene.label_filter {
+            LabelFilter::All => true,
+            LabelFilter::HoveredFace => match scene.hovered_face {
+                Some(face) => lbl.on_face(&scene.dims, &scene.cube.geometry.faces[face].dims, 1.0),
+                None => false
+            },
+            LabelFilter::Boundary => lbl.on_boundary(1.0),
+        };
+        let constraints = lbl.constraints(&scene.dims, 1.0).join(", ");
+        let matches = lbl.txt.to_lowercase().contains(&search) || constraints.to_lowercase().contains(&search);
+        if !shown || !matches {
+            continue;
+        }
+
+        if Selectable::new(&im_str!("{}##label{}", lbl.txt, ix)).selected(scene.selected_label == Some(ix)).build(ui) {
+            selected = Some(ix);
+        }
+        ui.text_disabled(if constraints.is_empty() { "everywhere".to_string() } else { constraints });
+    }
+
+    // Selecting a label swings the camera around to face it.
+    if let Some(ix) = selected {
+        if scene.selected_label == Some(ix) {
+            scene.selected_label = None;
+        } else {
+            scene.selected_label = Some(ix);
+            let target = scene.label_point(ix);
+            scene.camera.focus(target);
+        }
+    }
+}
+
+// A plain listing of every face, for when the picture gets too crowded to read.
+fn faces_ui(ui: &Ui, scene: &mut Scene) {
+    let geometry = &scene.cube.geometry;
+    if geometry.faces.is_empty() {
+        ui.text_disabled("This goal has no faces");
+        return;
+    }
+
+    // Coolttviz only gets told about the boundary through its labels, so a face is on the boundary if it lies inside a label that is
+    // pinned to an endpoint of some dimension. Labels that aren't pinned anywhere are for the inside of the goal, and cover every face.
+    ui.columns(4, im_str!("faces"), true);
+    for txt in &["Varying", "Fixed", "Labels", "Boundary"] {
+        ui.text(txt);
+        ui.next_column();
+    }
+    ui.separator();
+    for (ix, face) in geometry.faces.iter().enumerate() {
+        let [a, b] = face.varying;
+        let selected = scene.selected_face == Some(ix);
+        if Selectable::new(&im_str!("{}, {}##face{}", scene.dims[a], scene.dims[b], ix))
+            .selected(selected)
+            .flags(SelectableFlags::SPAN_ALL_COLUMNS)
+            .build(ui) {
+            scene.selected_face = if selected { None } else { Some(ix) };
+        }
+        ui.next_column();
+
+        let fixed: Vec<String> = face.dims.iter().map(|(nm, d)| format!("{} = {}", nm, if *d { 1 } else { 0 })).collect();
+        let sliced: Vec<String> = geometry.sliced.iter().map(|(nm, t)| format!("{} = {:.2}", nm, t)).collect();
+        ui.text([fixed, sliced].concat().join(", "));
+        ui.next_column();
+
+        let labels: Vec<&str> = scene.labels.iter().filter(|lbl| lbl.on_face(&scene.dims, &face.dims, 1.0)).map(|lbl| lbl.txt.as_str()).collect();
+        if labels.is_empty() {
+            ui.text_disabled("-");
+        } else {
+            ui.text(labels.join(", "));
+        }
+        ui.next_column();
+
+        // Say which parts of the boundary the face lies in, as in 'i = 0 ∨ j = 1'.
+        let mut boundary: Vec<String> = scene.labels.iter()
+            .filter(|lbl| lbl.on_boundary(1.0) && lbl.on_face(&scene.dims, &face.dims, 1.0))
+            .map(|lbl| lbl.constraints(&scene.dims, 1.0).join(" ∧ "))
+            .collect();
+        boundary.sort();
+        boundary.dedup();
+        if boundary.is_empty() {
+            ui.text_disabled("no");
+        } else {
+            ui.text(boundary.join(" ∨ "));
+        }
+        ui.next_column();
+    }
+    ui.columns(1, im_str!("faces"), false);
+}
+
+// Work out where every label is anchored on screen before laying them out, so that they can be moved out of each other's way.
+// Returns the index, anchor and alpha of every label that gets shown, along with the items to lay out.
+fn label_anchors(scene: &Scene, labels: &[(&label::Label, f32)], projection: &dyn linalg::Project, mvp: Matrix4<f32>, size: [f32; 2], selectable: bool) -> (Vec<(usize, [f32; 2], f32)>, Vec<layout::Item>) {
+    let model_eye = scene.cube.model.inverse_transform_point(&scene.camera.eye());
+    let radius = scene.cube.geometry.radius();
+    let mut anchors = Vec::new();
+    let mut items = Vec::new();
+    for (ix, (lbl, fade)) in labels.iter().enumerate() {
+        let on_slice = lbl.on_slice(&scene.options.embedding.slices, 1.0);
+        if !on_slice && !scene.options.dim_off_slice {
+            continue;
+        }
+        let projected = projection.project(&lbl.position);
+        let anchor = linalg::window_coords(mvp, size, projected);
+        // Labels tucked away behind the cube give way to the ones in front, and get drawn underneath them.
+        let distance = (model_eye.coords - projected).norm();
+        let occluded = scene.cube.geometry.intersections(model_eye, model_eye.coords - projected).iter()
+            .any(|(isect, _)| (model_eye - isect).norm() < distance - 1e-3);
+        let alpha = fade * label::depth_fade(distance, model_eye.coords.norm(), radius) * if on_slice { 1.0 } else { 0.4 };
+        anchors.push((ix, anchor, alpha));
+        // The selected label always wins.
+        let priority = if selectable && scene.selected_label == Some(ix) { 5.0 } else if occluded { 0.2 } else { 1.0 };
+        items.push(layout::Item { anchor, size: scene.text.measure(&lbl.display_text(scene.options.label_width)), priority });
+    }
+    (anchors, items)
+}
+
+// Draw the goal itself, along with everything pinned to it, like the labels and the axis annotations.
+// Picking only makes sense when there is a mouse to pick with.
+fn draw_scene<F: Facade, S: Surface>(ui: &Ui, display: &F, scene : &mut Scene, target: &mut S, picking: bool) {
+    scene.text.set_scale(ui.io().display_framebuffer_scale[0]);
+
+    let progress = scene.animate(display);
 
     let eye = scene.camera.eye();
     let view = scene.camera.view();
-
-    let aspect = width / height;
-    let fov = 45.0_f32.to_radians();
-    let projection = Perspective3::new(aspect, fov, 0.1, 100.0);
+    let projection = perspective(ui.io().display_size);
 
     let view_proj = projection.to_homogeneous() * view.to_homogeneous();
     let mvp = view_proj * scene.cube.model.to_homogeneous();
 
-    scene.cube.render(view_proj, &scene.program, target);
+    if scene.options.filled_faces {
+        scene.cube.render_filled(eye, view_proj, &scene.program, target);
+    }
+    // Corners are always drawn for points and lines, where the lines of the wireframe can't carry the picture on their own.
+    let show_vertices = scene.options.show_vertices || scene.cube.geometry.is_degenerate();
+    scene.cube.render(view_proj, show_vertices, &scene.program, target);
+
+    let on_net = net::OnNet { geometry: &scene.cube.geometry, dim_names: &scene.dims, size: 1.0 };
+    let morph;
+    let blended;
+    let (labels, label_projection): (Vec<(&label::Label, f32)>, &dyn linalg::Project) = match (&scene.transition, progress) {
+        (Some(old), Some(t)) => {
+            morph = transition::Morph { from: &old.embedding, from_dims: &old.dims, to: &scene.options.embedding, to_dims: &scene.dims, t };
+            blended = label::blend(&old.dims, &old.labels, &scene.dims, &scene.labels, transition::ease(t));
+            (blended.iter().map(|(lbl, fade)| (lbl, *fade)).collect(), &morph)
+        },
+        _ => {
+            let projection: &dyn linalg::Project = if scene.seams.is_empty() { &scene.options.embedding } else { &on_net };
+            (scene.labels.iter().map(|lbl| (lbl, 1.0)).collect(), projection)
+        }
+    };
 
-    for lbl in &scene.labels {
-        lbl.render(mvp, ui);
+    let (anchors, items) = label_anchors(scene, &labels, label_projection, mvp, ui.io().display_size, progress.is_none());
+    // The labels are different every frame of a transition, so there is nothing to keep stable.
+    let positions = if progress.is_some() { layout::Layout::default().place(&items) } else { scene.layout.place(&items) };
+
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    order.sort_by(|a, b| items[*a].priority.partial_cmp(&items[*b].priority).expect("Priority should not be NaN"));
+    let [mouse_x, mouse_y] = ui.io().mouse_pos;
+    let mut clicked = None;
+    for k in order {
+        let (ix, anchor, alpha) = anchors[k];
+        let highlighted = progress.is_none() && scene.selected_label == Some(ix);
+        let [min, max] = labels[ix].0.render(&mut scene.text, positions[k], anchor, scene.options.label_width, alpha, highlighted);
+        if min[0] <= mouse_x && mouse_x <= max[0] && min[1] <= mouse_y && mouse_y <= max[1] {
+            clicked = Some(ix);
+        }
+    }
+    // Clicking on a label toggles between the short and full versions of the term.
+    // Later labels get drawn on top, so they win when labels overlap.
+    if let (Some(ix), None) = (clicked, progress) {
+        if ui.is_mouse_clicked(MouseButton::Left) && !ui.io().want_capture_mouse {
+            scene.labels[ix].expanded = !scene.labels[ix].expanded;
+        }
     }
 
-    let mouse_view_point = view.inverse() * linalg::world_coords(projection, ui.io().display_size, ui.io().mouse_pos);
-    let direction = Unit::new_normalize(eye - mouse_view_point);
+    // Number the cut edges of the net, so that we can see which ones get glued back together.
+    if !scene.seams.is_empty() {
+        let draw_list = ui.get_background_draw_list();
+        for (edge, seam) in scene.cube.geometry.edges.iter().zip(&scene.seams) {
+            if let Some(n) = seam {
+                let [x, y] = linalg::window_coords(mvp, ui.io().display_size, (edge.points[0] + edge.points[1]) / 2.0);
+                draw_list.add_text([x + 4.0, y + 4.0], [0.8, 0.0, 0.0, 1.0], format!("{}", n));
+            }
+        }
+    }
 
-    let isects = scene.cube.intersections(eye, *direction);
-    if let Some((_, face)) = isects.first() {
-        scene.cube.render_face(face, view_proj, &scene.program, target);
+    if scene.options.annotate_axes {
+        annotate_axes(ui, scene, mvp, view);
+    }
+
+    if let Some(ix) = scene.selected_face {
+        scene.cube.render_face(ix, view_proj, &scene.program, target);
+    }
+
+    let pick =
+        if picking {
+            let mouse_view_point = view.inverse() * linalg::world_coords(projection, ui.io().display_size, ui.io().mouse_pos);
+            let direction = Unit::new_normalize(eye - mouse_view_point);
+            scene.cube.geometry.pick(eye, *direction)
+        } else {
+            None
+        };
+    if let Some(geometry::Pick::Face(ix)) = pick {
+        scene.hovered_face = Some(ix);
+    }
+    if let Some(pick) = pick {
+        scene.cube.render_pick(pick, view_proj, &scene.program, target);
         ui.tooltip(|| {
-            let mut s = String::new();
-            for (nm, d) in &face.dims {
-                s.push_str(&format!("{} = {}\n", nm, if *d { 1 } else { 0 }));
+            for (nm, d) in scene.cube.geometry.pick_dims(pick) {
+                colored_text(ui, scene, &format!("{} = {}", nm, if *d { 1 } else { 0 }));
+            }
+            for (nm, t) in &scene.cube.geometry.sliced {
+                colored_text(ui, scene, &format!("{} = {:.2}", nm, t));
+            }
+
+            // Everything the goal has to say about this face, including along its edges and at its corners.
+            if let geometry::Pick::Face(ix) = pick {
+                let face = &scene.cube.geometry.faces[ix];
+                let on_face: Vec<(&label::Label, geometry::Constraints)> = scene.labels.iter()
+                    .filter_map(|lbl| lbl.on_closed_face(&scene.dims, face.varying, &face.dims, 1.0).map(|edge| (lbl, edge)))
+                    .collect();
+                if !on_face.is_empty() {
+                    ui.separator();
+                }
+                for (lbl, edge) in on_face {
+                    ui.text(&lbl.txt);
+                    if !edge.is_empty() {
+                        let edge: Vec<String> = edge.iter().map(|(nm, d)| format!("{} = {}", nm, if *d { 1 } else { 0 })).collect();
+                        ui.text_disabled(format!("    at {}", edge.join(", ")));
+                    }
+                }
             }
-            ui.text(s);
         });
     };
 
-    let ctx = unsafe { ImStr::from_utf8_with_nul_unchecked(scene.context.as_bytes()) };
+    // Labels go on top of everything else in the scene.
+    scene.text.draw(display, target);
+
+    // Points and lines don't have any faces to hover over, so we always label their endpoints directly.
+    if show_vertices && (scene.options.vertex_coordinates || scene.cube.geometry.is_degenerate()) {
+        let draw_list = ui.get_background_draw_list();
+        for corner in &scene.cube.geometry.corners {
+            let txt: Vec<String> = corner.dims.iter().map(|(nm, d)| format!("{}={}", nm, if *d { 1 } else { 0 })).collect();
+            let [x, y] = linalg::window_coords(mvp, ui.io().display_size, corner.point);
+            draw_list.add_text([x + 6.0, y + 6.0], [0.0, 0.0, 0.0, 1.0], txt.join(" "));
+        }
+    }
+}
+
+// Flatten the scene down into a drawing of what the camera sees, in a window of the given size.
+fn drawing(scene: &Scene, size: [f32; 2]) -> svg::Drawing {
+    let view_proj = perspective(size).to_homogeneous() * scene.camera.view().to_homogeneous();
+    let mvp = view_proj * scene.cube.model.to_homogeneous();
+    let model_eye = scene.cube.model.inverse_transform_point(&scene.camera.eye());
+    let depth = |p: Vector3<f32>| (model_eye.coords - p).norm();
+    let screen = |p: Vector3<f32>| linalg::window_coords(mvp, size, p);
+    let geometry = &scene.cube.geometry;
+    let mut drawing = svg::Drawing::new(size);
+
+    let quad = |face: &geometry::Face| -> Vec<[f32; 2]> { [0, 1, 3, 2].iter().map(|k| screen(face.points[*k])).collect() };
+    let centroid = |face: &geometry::Face| face.points.iter().sum::<Vector3<f32>>() / 4.0;
+    if scene.options.filled_faces {
+        for (face, color) in geometry.faces.iter().zip(&scene.cube.face_colors) {
+            drawing.add(depth(centroid(face)), svg::Shape::Polygon { points: quad(face), color: *color });
+        }
+    }
+    if let Some(ix) = scene.selected_face {
+        let face = &geometry.faces[ix];
+        drawing.add(depth(centroid(face)), svg::Shape::Polygon { points: quad(face), color: [1.0, 0.0, 0.0, 0.5] });
+    }
+
+    for (edge, color) in geometry.edges.iter().zip(&scene.cube.edge_colors) {
+        if color[3] > 0.0 {
+            let [from, to] = edge.points;
+            drawing.add(depth((from + to) / 2.0), svg::Shape::Line { from: screen(from), to: screen(to), color: *color, width: 1.5 });
+        }
+    }
+
+    let black = [0.0, 0.0, 0.0, 1.0];
+    if scene.options.show_vertices || geometry.is_degenerate() {
+        for corner in &geometry.corners {
+            drawing.add(depth(corner.point), svg::Shape::Circle { center: screen(corner.point), radius: 4.0, color: black, filled: true });
+            if scene.options.vertex_coordinates || geometry.is_degenerate() {
+                let txt: Vec<String> = corner.dims.iter().map(|(nm, d)| format!("{}={}", nm, if *d { 1 } else { 0 })).collect();
+                let txt = txt.join(" ");
+                let [x, y] = screen(corner.point);
+                let [w, h] = scene.text.measure(&txt);
+                drawing.overlay(svg::Shape::Text { min: [x + 6.0, y + 6.0], max: [x + 6.0 + w, y + 6.0 + h], txt, color: black, background: [1.0, 1.0, 1.0, 0.0] });
+            }
+        }
+    }
+
+    for placed in placed_labels(scene, mvp, size) {
+        let highlighted = scene.selected_label == Some(placed.ix);
+        let bounds = [placed.pos, [placed.pos[0] + placed.size[0], placed.pos[1] + placed.size[1]]];
+        scene.labels[placed.ix].render_svg(&mut drawing, bounds, placed.anchor, scene.options.label_width, placed.alpha, highlighted);
+    }
+
+    drawing
+}
+
+// A label that has found a spot on the screen.
+struct PlacedLabel {
+    ix: usize,
+    anchor: [f32; 2],
+    pos: [f32; 2],
+    size: [f32; 2],
+    alpha: f32
+}
+
+// Lay the labels out in the same way as on screen, starting from wherever they are on screen right now.
+// These come back in the order they should be drawn in, so that the more important labels go on top.
+fn placed_labels(scene: &Scene, mvp: Matrix4<f32>, size: [f32; 2]) -> Vec<PlacedLabel> {
+    let on_net = net::OnNet { geometry: &scene.cube.geometry, dim_names: &scene.dims, size: 1.0 };
+    let projection: &dyn linalg::Project = if scene.seams.is_empty() { &scene.options.embedding } else { &on_net };
+    let labels: Vec<(&label::Label, f32)> = scene.labels.iter().map(|lbl| (lbl, 1.0)).collect();
+    let (anchors, items) = label_anchors(scene, &labels, projection, mvp, size, true);
+    let mut layout = scene.layout.clone();
+    let mut positions = Vec::new();
+    for _ in 0..SETTLING_FRAMES {
+        positions = layout.place(&items);
+    }
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    order.sort_by(|a, b| items[*a].priority.partial_cmp(&items[*b].priority).expect("Priority should not be NaN"));
+    order.into_iter().map(|k| {
+        let (ix, anchor, alpha) = anchors[k];
+        PlacedLabel { ix, anchor, pos: positions[k], size: items[k].size, alpha }
+    }).collect()
+}
+
+// Lay the goal out as a TikZ picture of what the camera sees.
+// Squares ignore the camera, and get drawn flat with the first axis running left to right and the second running up, like in a paper.
+fn tikz_picture(scene: &Scene, size: [f32; 2]) -> tikz::Picture {
+    let mvp = perspective(size).to_homogeneous() * scene.camera.view().to_homogeneous() * scene.cube.model.to_homogeneous();
+    let geometry = &scene.cube.geometry;
+    let axis_dims = scene.axis_dims();
+    let square = axis_dims.len() == 2 && scene.seams.is_empty();
+    let mut picture = tikz::Picture::new(square);
+
+    // Squares get laid out on a grid running from 0 to 1, going down the page like window coordinates do.
+    let at = |dims: &geometry::Constraints, point: Vector3<f32>| {
+        if square {
+            let coord = |nm: &String| match dims.iter().find(|(dim, _)| dim == nm) {
+                Some((_, true)) => 1.0,
+                _ => 0.0
+            };
+            [coord(&axis_dims[0]), 1.0 - coord(&axis_dims[1])]
+        } else {
+            linalg::window_coords(mvp, size, point)
+        }
+    };
+    let with = |dims: &geometry::Constraints, dim: usize, d: bool| {
+        let mut dims = dims.clone();
+        dims.push((scene.dims[dim].clone(), d));
+        dims
+    };
+
+    if scene.options.filled_faces {
+        for (face, color) in geometry.faces.iter().zip(&scene.cube.face_colors) {
+            let [a, b] = face.varying;
+            let mut corners = [0; 4];
+            for (k, corner) in corners.iter_mut().enumerate() {
+                let dims = with(&with(&face.dims, a, k % 2 == 1), b, k >= 2);
+                *corner = picture.vertex(&scene.dims, &dims, at(&dims, face.points[k]));
+            }
+            picture.faces.push(tikz::Face { corners, color: *color });
+        }
+    }
+    for (edge, color) in geometry.edges.iter().zip(&scene.cube.edge_colors) {
+        if color[3] <= 0.0 {
+            continue;
+        }
+        let [from, to] = [false, true].map(|d| with(&edge.dims, edge.varying, d));
+        let from = picture.vertex(&scene.dims, &from, at(&from, edge.points[0]));
+        let to = picture.vertex(&scene.dims, &to, at(&to, edge.points[1]));
+        picture.edges.push(tikz::Edge { from, to, dim: scene.dims[edge.varying].clone(), color: *color, txt: Vec::new() });
+    }
+    for corner in &geometry.corners {
+        picture.vertex(&scene.dims, &corner.dims, at(&corner.dims, corner.point));
+    }
+
+    if square {
+        // Everything goes right where it lives on the square, and whatever doesn't land on a vertex or an edge goes where it is in the middle.
+        let slot = |nm: &String| scene.dims.iter().position(|dim| dim == nm).expect("Axis dimensions should be dimensions");
+        let [a, b] = [slot(&axis_dims[0]), slot(&axis_dims[1])];
+        for lbl in scene.labels.iter().filter(|lbl| lbl.on_slice(&scene.options.embedding.slices, 1.0)) {
+            let pos = [(lbl.position[a] + 1.0) / 2.0, 1.0 - (lbl.position[b] + 1.0) / 2.0];
+            if !picture.attach(pos, &lbl.txt) {
+                picture.labels.push(tikz::Label { anchor: pos, pos, txt: lbl.txt.clone() });
+            }
+        }
+    } else {
+        for placed in placed_labels(scene, mvp, size) {
+            picture.labels.push(tikz::Label { anchor: placed.anchor, pos: placed.pos, txt: scene.labels[placed.ix].display_text(scene.options.label_width) });
+        }
+    }
+    picture
+}
+
+// The projected cube as solid geometry, with the edges as thin tubes, and the labels as markers where they sit.
+// Unlike the pictures, this doesn't care where the camera is.
+fn meshes(scene: &Scene) -> (Vec<mesh::Mesh>, Vec<mesh::Marker>) {
+    let world = |p: Vector3<f32>| scene.cube.model.transform_point(&Point3::from(p));
+    let meshes = mesh::cube(&scene.cube.geometry, &scene.dims, &scene.cube.face_colors, &scene.cube.edge_colors, scene.options.filled_faces, world);
+
+    let markers = (0..scene.labels.len())
+        .filter(|ix| scene.labels[*ix].on_slice(&scene.options.embedding.slices, 1.0))
+        .map(|ix| {
+            let p = scene.label_point(ix);
+            mesh::Marker { name: scene.labels[ix].display_text(scene.options.label_width), position: [p.x, p.y, p.z], txt: scene.labels[ix].txt.clone() }
+        })
+        .collect();
+    (meshes, markers)
+}
+
+// Write what the camera sees out to a file, in whichever format the extension asks for.
+// OBJ keeps its colours in a second file next to the first.
+fn export(scene: &Scene, path: &Path, size: [f32; 2]) -> Result<(), String> {
+    let files = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("svg") => vec![(path.to_path_buf(), drawing(scene, size).to_svg())],
+        Some("tex") | Some("tikz") => vec![(path.to_path_buf(), tikz_picture(scene, size).to_tikz())],
+        Some("obj") => {
+            let (meshes, markers) = meshes(scene);
+            let mtl_path = path.with_extension("mtl");
+            let mtl_file = mtl_path.file_name().and_then(|nm| nm.to_str()).unwrap_or("goal.mtl");
+            let (obj, mtl) = mesh::to_obj(&meshes, &markers, mtl_file);
+            vec![(path.to_path_buf(), obj), (mtl_path, mtl)]
+        },
+        Some("gltf") => {
+            let (meshes, markers) = meshes(scene);
+            vec![(path.to_path_buf(), mesh::to_gltf(&meshes, &markers))]
+        },
+        _ => return Err(format!("Don't know how to export to {}", path.display()))
+    };
+    for (path, contents) in files {
+        std::fs::write(&path, contents).map_err(|err| format!("Couldn't write {}: {}", path.display(), err))?;
+    }
+    Ok(())
+}
+
+fn export_ui(ui: &Ui, scene: &mut Scene) {
+    InputText::new(ui, im_str!("File"), &mut scene.options.export_name).resize_buffer(true).build();
+    let mut format = None;
+    if ui.small_button(im_str!("Export SVG")) {
+        format = Some("svg");
+    }
+    ui.same_line_with_spacing(0.0, -1.0);
+    if ui.small_button(im_str!("Export TikZ")) {
+        format = Some("tex");
+    }
+    if ui.small_button(im_str!("Export OBJ")) {
+        format = Some("obj");
+    }
+    ui.same_line_with_spacing(0.0, -1.0);
+    if ui.small_button(im_str!("Export glTF")) {
+        format = Some("gltf");
+    }
+    if let Some(format) = format {
+        let path = Path::new(scene.options.export_name.to_str()).with_extension(format);
+        scene.export_status = Some(match export(scene, &path, ui.io().display_size) {
+            Ok(()) => format!("Wrote {}", path.display()),
+            Err(err) => err
+        });
+    }
+    if let Some(status) = &scene.export_status {
+        ui.text_disabled(status);
+    }
+}
+
+fn context_ui(ui: &Ui, scene: &Scene) {
     Window::new(im_str!("Context"))
         .size([200.0, 200.0], Condition::Appearing)
+        .horizontal_scrollbar(true)
+        .build(ui, || {
+            if let Some(err) = &scene.message_error {
+                ui.text_colored([0.9, 0.2, 0.2, 1.0], err);
+                ui.separator();
+            }
+            for line in scene.context.trim_end_matches('\0').lines() {
+                colored_text(ui, scene, line);
+            }
+        });
+}
+
+fn render_frame<F: Facade, S: Surface>(ui: &Ui, display: &F, scene : &mut Scene, target: &mut S) {
+    let [width, height] = ui.io().display_size;
+
+    draw_scene(ui, display, scene, target, true);
+    context_ui(ui, scene);
+
+    let mut rebuild = false;
+    let axis_dims = scene.axis_dims();
+    Window::new(im_str!("View"))
+        .size([280.0, 240.0], Condition::Appearing)
+        .position([width - 300.0, 20.0], Condition::Appearing)
+        .build(ui, || {
+            let options = &mut scene.options;
+            ui.checkbox(im_str!("Fill faces"), &mut options.filled_faces);
+            rebuild |= ui.checkbox(im_str!("Unfold into a net"), &mut options.unfolded);
+            ui.checkbox(im_str!("Name the axes"), &mut options.annotate_axes);
+            let mut width = options.label_width as u32;
+            if Slider::new(im_str!("Label width")).range(4..=80).build(ui, &mut width) {
+                options.label_width = width as usize;
+            }
+            ui.checkbox(im_str!("Show vertices"), &mut options.show_vertices);
+            if options.show_vertices {
+                ui.same_line_with_spacing(0.0, -1.0);
+                ui.checkbox(im_str!("Coordinates"), &mut options.vertex_coordinates);
+            }
+            rebuild |= projection_ui(ui, &mut options.embedding.projection, &axis_dims);
+            if CollapsingHeader::new(im_str!("Rotation")).build(ui) {
+                rebuild |= rotation_ui(ui, &mut options.embedding.rotation, &mut options.rotation_dim, &axis_dims);
+            }
+            if CollapsingHeader::new(im_str!("Camera")).build(ui) {
+                camera_ui(ui, display, scene);
+            }
+            if CollapsingHeader::new(im_str!("Export")).build(ui) {
+                export_ui(ui, scene);
+            }
+        });
+
+    Window::new(im_str!("Dimensions"))
+        .size([200.0, 160.0], Condition::Appearing)
+        .position([width - 300.0, 280.0], Condition::Appearing)
+        .build(ui, || {
+            let embedding = &mut scene.options.embedding;
+            rebuild |= axes_ui(ui, &mut embedding.axes, &embedding.slices, &scene.dims);
+            if CollapsingHeader::new(im_str!("Slices")).build(ui) {
+                rebuild |= slices_ui(ui, &mut embedding.slices, &scene.dims);
+                ui.checkbox(im_str!("Dim labels off the slice"), &mut scene.options.dim_off_slice);
+            }
+        });
+
+    Window::new(im_str!("Legend"))
+        .size([200.0, 160.0], Condition::Appearing)
+        .position([width - 300.0, 460.0], Condition::Appearing)
         .build(ui, || {
-            ui.text_wrapped(ctx)
+            rebuild |= legend_ui(ui, &mut scene.options, &scene.dims);
         });
+
+    Window::new(im_str!("Labels"))
+        .size([280.0, 300.0], Condition::Appearing)
+        .position([240.0, 20.0], Condition::Appearing)
+        .collapsed(true, Condition::FirstUseEver)
+        .build(ui, || labels_ui(ui, scene));
+
+    Window::new(im_str!("Faces"))
+        .size([420.0, 240.0], Condition::Appearing)
+        .position([20.0, height - 260.0], Condition::Appearing)
+        .collapsed(true, Condition::FirstUseEver)
+        .build(ui, || faces_ui(ui, scene));
+
+    if rebuild {
+        scene.rebuild(display);
+    }
 }
 
-fn handle_input(ui: &Ui, scene: &mut Scene) {
+// The keys for the bookmarks, in order.
+const BOOKMARK_KEYS: [VirtualKeyCode; 10] = [
+    VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3, VirtualKeyCode::Key4, VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6, VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9, VirtualKeyCode::Key0,
+];
+
+// The keys for the preset views, in the same order as 'camera::PRESETS'.
+const PRESET_KEYS: [VirtualKeyCode; 4] = [VirtualKeyCode::F, VirtualKeyCode::T, VirtualKeyCode::S, VirtualKeyCode::I];
+
+fn handle_input<F: Facade>(ui: &Ui, display: &F, scene: &mut Scene) {
     let io = ui.io();
+    scene.camera.update();
+
+    if !io.want_capture_keyboard {
+        for (ix, key) in PRESET_KEYS.iter().enumerate() {
+            if ui.is_key_index_pressed(*key as _) {
+                let view = scene.camera.preset(ix);
+                scene.camera.animate_to(view);
+            }
+        }
+        if ui.is_key_index_pressed(VirtualKeyCode::Home as _) {
+            scene.fit_view();
+        }
+        for (ix, key) in BOOKMARK_KEYS.iter().enumerate() {
+            if ui.is_key_index_pressed(*key as _) {
+                if io.key_ctrl {
+                    scene.save_bookmark(ix);
+                } else {
+                    scene.restore_bookmark(ix, display);
+                }
+            }
+        }
+    }
+
     if !io.want_capture_mouse {
         let [delta_x, delta_y] = io.mouse_delta;
-        if ui.is_mouse_down(MouseButton::Left) {
+        let rotation_dim = scene.options.rotation_dim;
+        let modified = io.key_shift || io.key_ctrl;
+        if ui.is_mouse_down(MouseButton::Left) && modified && rotation_dim < scene.axis_dims().len() {
+            // Turn the cube in the planes between the screen axes and a higher dimension,
+            // so that we can see the faces that are otherwise squashed up inside.
+            if delta_x != 0.0 || delta_y != 0.0 {
+                let rotation = &mut scene.options.embedding.rotation;
+                if io.key_shift {
+                    rotation.rotate(0, rotation_dim, delta_x / 300.0);
+                    rotation.rotate(1, rotation_dim, delta_y / 300.0);
+                } else {
+                    rotation.rotate(2, rotation_dim, delta_x / 300.0);
+                }
+                scene.rebuild(display);
+            }
+        } else if ui.is_mouse_down(MouseButton::Left) && scene.camera.arcball() {
+            let [x, y] = io.mouse_pos;
+            scene.camera.rotate_arcball([x - delta_x, y - delta_y], [x, y], io.display_size);
+        } else if ui.is_mouse_down(MouseButton::Left) {
             scene.camera.rotate_azimuth(delta_x / 300.0);
             scene.camera.rotate_polar(delta_y / 300.0);
+        } else if ui.is_mouse_down(MouseButton::Right) || ui.is_mouse_down(MouseButton::Middle) {
+            scene.camera.pan(delta_x, delta_y);
         }
         scene.camera.zoom(0.1_f32 * io.mouse_wheel);
     }
 }
 
-fn handle_message(msg: messages::Message, display: &Display, scene: &mut Scene) {
+fn handle_message<F: Facade>(msg: messages::Message, display: &F, scene: &mut Scene) {
+    scene.message_error = None;
     match msg {
-        messages::Message::DisplayGoal(goal) =>
-            *scene = init_scene(display, &goal)
+        messages::Message::DisplayGoal(goal) => {
+            let mut options = scene.options.clone();
+            options.embedding.axes = match_axes(&scene.dims, &scene.options.embedding.axes, &goal.dims);
+            options.embedding.slices = goal.dims.iter().map(|nm| {
+                match scene.dims.iter().position(|old| old == nm) {
+                    Some(ix) => scene.options.embedding.slice(ix),
+                    None => linalg::Slice::Free
+                }
+            }).collect();
+            let old = Transition {
+                started: Instant::now(),
+                dims: std::mem::take(&mut scene.dims),
+                embedding: scene.options.embedding.clone(),
+                labels: std::mem::take(&mut scene.labels),
+            };
+            let camera = std::mem::replace(&mut scene.camera, camera::Camera::new());
+            *scene = init_scene(display, &goal, options);
+            // Keep looking from the same place, so that we can see what changed.
+            scene.camera = camera;
+            // The net gets laid out from scratch for every goal, so there is nothing sensible to animate it from.
+            if scene.seams.is_empty() {
+                scene.transition = Some(old);
+            }
+        }
+        messages::Message::SetCamera(cam) => {
+            let camera = &mut scene.camera;
+            let mut view =
+                if cam.azimuth.is_some() || cam.polar.is_some() {
+                    let azimuth = cam.azimuth.map(f32::to_radians).unwrap_or_else(|| camera.azimuth());
+                    let polar = cam.polar.map(f32::to_radians).unwrap_or_else(|| camera.polar());
+                    camera.looking_from(azimuth, polar)
+                } else {
+                    camera.current()
+                };
+            if let Some([x, y, z]) = cam.target {
+                view.target = Point3::new(x, y, z);
+            }
+            if let Some(radius) = cam.radius {
+                view.radius = radius.max(0.1);
+            }
+            camera.animate_to(view);
+
+            if cam.rotation.is_some() {
+                match cam.rotate(&scene.options.embedding.rotation, &scene.axis_dims()) {
+                    Ok(rotation) => {
+                        scene.options.embedding.rotation = rotation;
+                        scene.rebuild(display);
+                    },
+                    Err(err) => scene.message_error = Some(err)
+                }
+            }
+        }
     }
 }
 
@@ -108,9 +1302,41 @@ pub fn render() {
     let dims = vec!["i".to_string(), "j".to_string(), "k".to_string(), "l".to_string()];
 
     let ctx = "Welcome to coolttviz!\nPlease add a #viz hole to your code to start visualizing your goals.\0";
-    let scene = init_scene(&system.display, &messages::DisplayGoal { dims, labels: vec![], context: ctx.to_string() });
+    let scene = init_scene(&system.display, &messages::DisplayGoal { dims, labels: vec![], context: ctx.to_string() }, Options::default());
     system.main_loop(scene, handle_message, |_, display, scene, target, ui| {
-        handle_input(ui, scene);
-        render_frame(ui, scene, target);
+        handle_input(ui, display, scene);
+        render_frame(ui, display, scene, target);
     })
 }
+
+// Draw a goal straight to a file without opening a window, as a PNG or anything else that 'export' knows about.
+pub fn render_to_file(goal: &Path, out: &Path, [width, height]: [u32; 2]) -> Result<(), String> {
+    let json = std::fs::read_to_string(goal).map_err(|err| format!("Couldn't read {}: {}", goal.display(), err))?;
+    let script: messages::Script = serde_json::from_str(&json).map_err(|err| format!("Couldn't parse {}: {}", goal.display(), err))?;
+    let mut msgs = script.messages().into_iter();
+    let first = match msgs.next() {
+        Some(messages::Message::DisplayGoal(goal)) => goal,
+        _ => return Err(format!("{} should start with a goal", goal.display()))
+    };
+
+    let mut headless = system::init_headless(width, height)?;
+    let mut scene = init_scene(&headless.renderer, &first, Options::default());
+    for msg in msgs {
+        handle_message(msg, &headless.renderer, &mut scene);
+        if let Some(err) = scene.message_error.take() {
+            return Err(format!("{}: {}", goal.display(), err));
+        }
+    }
+    // Nobody is watching, so skip straight to the end of any animations.
+    scene.transition = None;
+    scene.camera.finish_animation();
+
+    if out.extension().and_then(|ext| ext.to_str()) != Some("png") {
+        return export(&scene, out, [width as f32, height as f32]);
+    }
+    let pixels = headless.render(&mut scene, SETTLING_FRAMES, |display, scene, target, ui| {
+        draw_scene(ui, display, scene, target, false);
+        context_ui(ui, scene);
+    })?;
+    image::save_buffer(out, &pixels, width, height, image::ColorType::Rgba8).map_err(|err| format!("Couldn't write {}: {}", out.display(), err))
+}
@@ -0,0 +1,184 @@
+use std::cmp::Reverse;
+use std::fmt::Write;
+
+use ordered_float::NotNan;
+
+// The same font and padding that labels get on screen.
+const FONT_FAMILY: &str = "M PLUS 1p, sans-serif";
+const FONT_SIZE: f32 = 13.0;
+const PADDING: f32 = 3.0;
+
+// Something to draw, in window coordinates.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    Line { from: [f32; 2], to: [f32; 2], color: [f32; 4], width: f32 },
+    Polygon { points: Vec<[f32; 2]>, color: [f32; 4] },
+    Circle { center: [f32; 2], radius: f32, color: [f32; 4], filled: bool },
+    // Text in a box, with the top left corner of the box at 'min'.
+    Text { min: [f32; 2], max: [f32; 2], txt: String, color: [f32; 4], background: [f32; 4] }
+}
+
+// A flat picture of the scene, as the camera sees it.
+// Shapes in the scene get sorted so that the ones further away are drawn first, and anything in the overlay goes on top in the order it was added.
+#[derive(Clone, Debug, Default)]
+pub struct Drawing {
+    size: [f32; 2],
+    scene: Vec<(NotNan<f32>, Shape)>,
+    overlay: Vec<Shape>
+}
+
+fn color(color: [f32; 4]) -> String {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", channel(color[0]), channel(color[1]), channel(color[2]))
+}
+
+fn opacity(attr: &str, color: [f32; 4]) -> String {
+    if color[3] < 1.0 {
+        format!(" {}=\"{:.3}\"", attr, color[3].max(0.0))
+    } else {
+        String::new()
+    }
+}
+
+fn escape(txt: &str) -> String {
+    let mut escaped = String::with_capacity(txt.len());
+    for c in txt.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            // Control characters aren't allowed in XML at all.
+            c if c.is_control() && c != '\t' => {},
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+fn write_shape(out: &mut String, shape: &Shape) {
+    match shape {
+        Shape::Line { from, to, color: c, width } => {
+            writeln!(
+                out,
+                "  <line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke=\"{}\"{} stroke-width=\"{}\" stroke-linecap=\"round\"/>",
+                from[0], from[1], to[0], to[1], color(*c), opacity("stroke-opacity", *c), width
+            ).unwrap();
+        },
+        Shape::Polygon { points, color: c } => {
+            let points: Vec<String> = points.iter().map(|[x, y]| format!("{:.2},{:.2}", x, y)).collect();
+            writeln!(out, "  <polygon points=\"{}\" fill=\"{}\"{}/>", points.join(" "), color(*c), opacity("fill-opacity", *c)).unwrap();
+        },
+        Shape::Circle { center, radius, color: c, filled: true } => {
+            writeln!(out, "  <circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{}\" fill=\"{}\"{}/>", center[0], center[1], radius, color(*c), opacity("fill-opacity", *c)).unwrap();
+        },
+        Shape::Circle { center, radius, color: c, filled: false } => {
+            writeln!(
+                out,
+                "  <circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{}\" fill=\"none\" stroke=\"{}\"{} stroke-width=\"2\"/>",
+                center[0], center[1], radius, color(*c), opacity("stroke-opacity", *c)
+            ).unwrap();
+        },
+        Shape::Text { min, max, txt, color: c, background } => {
+            writeln!(
+                out,
+                "  <rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"{}\"{}/>",
+                min[0], min[1], max[0] - min[0], max[1] - min[1], color(*background), opacity("fill-opacity", *background)
+            ).unwrap();
+            // Keep the text as text, one line to a 'tspan', so that it can still be edited and searched.
+            let lines: Vec<&str> = txt.lines().collect();
+            let line_height = (max[1] - min[1] - 2.0 * PADDING) / lines.len().max(1) as f32;
+            write!(out, "  <text fill=\"{}\"{} xml:space=\"preserve\">", color(*c), opacity("fill-opacity", *c)).unwrap();
+            for (ix, line) in lines.iter().enumerate() {
+                // Roughly where the baseline sits, as the font's ascent is about four fifths of its height.
+                let y = min[1] + PADDING + (ix as f32 + 0.8) * line_height;
+                write!(out, "<tspan x=\"{:.2}\" y=\"{:.2}\">{}</tspan>", min[0] + PADDING, y, escape(line)).unwrap();
+            }
+            writeln!(out, "</text>").unwrap();
+        }
+    }
+}
+
+impl Drawing {
+    pub fn new(size: [f32; 2]) -> Drawing {
+        Drawing { size, ..Default::default() }
+    }
+
+    // Add a shape to the scene, 'depth' away from the camera.
+    pub fn add(&mut self, depth: f32, shape: Shape) {
+        self.scene.push((NotNan::new(depth).expect("Depth should not be NaN"), shape));
+    }
+
+    pub fn overlay(&mut self, shape: Shape) {
+        self.overlay.push(shape);
+    }
+
+    // Everything we are going to draw, from back to front.
+    pub fn shapes(&self) -> Vec<&Shape> {
+        let mut scene: Vec<&(NotNan<f32>, Shape)> = self.scene.iter().collect();
+        // The sort is stable, so shapes at the same depth stay in the order they were added.
+        scene.sort_by_key(|(depth, _)| Reverse(*depth));
+        scene.into_iter().map(|(_, shape)| shape).chain(&self.overlay).collect()
+    }
+
+    pub fn to_svg(&self) -> String {
+        let [width, height] = self.size;
+        let mut out = String::new();
+        writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>").unwrap();
+        writeln!(
+            out,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" font-family=\"{}\" font-size=\"{}\">",
+            width, height, width, height, FONT_FAMILY, FONT_SIZE
+        ).unwrap();
+        writeln!(out, "  <rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>").unwrap();
+        for shape in self.shapes() {
+            write_shape(&mut out, shape);
+        }
+        writeln!(out, "</svg>").unwrap();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(x: f32) -> Shape {
+        Shape::Line { from: [x, 0.0], to: [x, 10.0], color: [0.0, 0.0, 0.0, 1.0], width: 1.0 }
+    }
+
+    #[test]
+    fn back_to_front() {
+        let mut drawing = Drawing::new([100.0, 100.0]);
+        drawing.overlay(line(0.0));
+        drawing.add(1.0, line(1.0));
+        drawing.add(3.0, line(3.0));
+        drawing.add(2.0, line(2.0));
+        drawing.add(3.0, line(4.0));
+        assert_eq!(drawing.shapes(), vec![&line(3.0), &line(4.0), &line(2.0), &line(1.0), &line(0.0)]);
+    }
+
+    #[test]
+    fn text_is_text() {
+        let mut drawing = Drawing::new([100.0, 100.0]);
+        drawing.overlay(Shape::Text {
+            min: [10.0, 10.0],
+            max: [60.0, 46.0],
+            txt: "a < b && c\nλ i → p i".to_string(),
+            color: [0.0, 0.0, 0.0, 1.0],
+            background: [1.0, 1.0, 1.0, 0.8]
+        });
+        let svg = drawing.to_svg();
+        assert!(svg.contains(">a &lt; b &amp;&amp; c</tspan>"));
+        assert!(svg.contains(">λ i → p i</tspan>"));
+        assert!(svg.contains("fill=\"#ffffff\" fill-opacity=\"0.800\""));
+        assert!(svg.starts_with("<?xml") && svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(color([0.0, 0.447, 0.698, 1.0]), "#0072b2");
+        assert_eq!(color([2.0, -1.0, 1.0, 1.0]), "#ff00ff");
+        assert_eq!(opacity("fill-opacity", [0.0, 0.0, 0.0, 1.0]), "");
+    }
+}
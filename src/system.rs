@@ -2,11 +2,15 @@ use glium::glutin;
 use glium::glutin::event::{Event, WindowEvent};
 use glium::glutin::event_loop::{ControlFlow, EventLoop};
 use glium::glutin::window::WindowBuilder;
+use glium::texture::{MipmapsOption, RawImage2d, SrgbFormat, SrgbTexture2d};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::backend;
 use glium::{Display, Frame, Surface};
 use imgui::{Context, FontConfig, FontGlyphRanges, FontSource, Ui};
 use imgui_glium_renderer::Renderer;
 use imgui_winit_support::{HiDpiMode, WinitPlatform};
 use std::path::Path;
+use std::rc::Rc;
 use std::time::Instant;
 
 use crate::server::Server;
@@ -22,6 +26,31 @@ pub struct System {
     pub server: Server
 }
 
+// Load the fonts, returning the size they ended up at.
+fn add_fonts(imgui: &mut Context, hidpi_factor: f64) -> f32 {
+    let font_size = (13.0 * hidpi_factor) as f32;
+    imgui.fonts().add_font(&[
+        FontSource::DefaultFontData {
+            config: Some(FontConfig {
+                size_pixels: font_size,
+                ..FontConfig::default()
+            }),
+        },
+        FontSource::TtfData {
+            data: include_bytes!("../resources/mplus-1p-regular.ttf"),
+            size_pixels: font_size,
+            config: Some(FontConfig {
+                rasterizer_multiply: 1.75,
+                glyph_ranges: FontGlyphRanges::from_slice(&[1,0x1FFFF,0]),
+                ..FontConfig::default()
+            }),
+        },
+    ]);
+
+    imgui.io_mut().font_global_scale = (1.0 / hidpi_factor) as f32;
+    font_size
+}
+
 pub fn init(port: u32, title: &str) -> System {
     let title = match Path::new(&title).file_name() {
         Some(file_name) => file_name.to_str().unwrap(),
@@ -45,27 +74,7 @@ pub fn init(port: u32, title: &str) -> System {
         platform.attach_window(imgui.io_mut(), window, HiDpiMode::Rounded);
     }
 
-    let hidpi_factor = platform.hidpi_factor();
-    let font_size = (13.0 * hidpi_factor) as f32;
-    imgui.fonts().add_font(&[
-        FontSource::DefaultFontData {
-            config: Some(FontConfig {
-                size_pixels: font_size,
-                ..FontConfig::default()
-            }),
-        },
-        FontSource::TtfData {
-            data: include_bytes!("../resources/mplus-1p-regular.ttf"),
-            size_pixels: font_size,
-            config: Some(FontConfig {
-                rasterizer_multiply: 1.75,
-                glyph_ranges: FontGlyphRanges::from_slice(&[1,0x1FFFF,0]),
-                ..FontConfig::default()
-            }),
-        },
-    ]);
-
-    imgui.io_mut().font_global_scale = (1.0 / hidpi_factor) as f32;
+    let font_size = add_fonts(&mut imgui, platform.hidpi_factor());
 
     let renderer = Renderer::init(&mut imgui, &display).expect("Failed to initialize renderer");
 
@@ -132,7 +141,7 @@ impl System {
                 let draw_data = ui.render();
                 renderer
                     .render(&mut target, draw_data)
-                    .expect("Rendering failed");
+                    .map_err(|err| format!("Rendering failed: {}", err))?;
                 target.finish().expect("Failed to swap buffers");
             }
             Event::WindowEvent {
@@ -145,3 +154,83 @@ impl System {
         }})
     }
 }
+
+// Everything we need to draw frames without a window, for rendering goals from scripts.
+pub struct Headless {
+    pub renderer: Rc<backend::Context>,
+    pub imgui: Context,
+    pub imgui_renderer: Renderer,
+    pub width: u32,
+    pub height: u32
+}
+
+// Both of these do all of their rendering in software when there isn't a GPU, so this works in containers without a display.
+// OSMesa comes first since it's what we have always used, but plenty of distros have stopped shipping it, so EGL with llvmpipe is the fallback.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn headless_context(width: u32, height: u32) -> Result<Rc<backend::Context>, String> {
+    use glium::backend::Facade;
+    use glium::glutin::platform::unix::HeadlessContextExt;
+    use glium::HeadlessRenderer;
+
+    let osmesa = glutin::ContextBuilder::new()
+        .build_osmesa(glutin::dpi::PhysicalSize::new(width, height))
+        .map_err(|err| format!("Failed to create an OSMesa context, is libOSMesa installed? {}", err))
+        .and_then(|context| HeadlessRenderer::new(context).map_err(|err| format!("Failed to initialize OSMesa: {}", err)));
+    match osmesa {
+        Ok(renderer) => Ok(renderer.get_context().clone()),
+        Err(osmesa_err) => crate::egl::surfaceless(width, height)
+            .map_err(|egl_err| format!("{}\nFalling back to EGL didn't work either: {}", osmesa_err, egl_err))
+    }
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn headless_context(_width: u32, _height: u32) -> Result<Rc<backend::Context>, String> {
+    Err("Headless rendering uses OSMesa or EGL, which are only available on Linux and the BSDs".to_string())
+}
+
+pub fn init_headless(width: u32, height: u32) -> Result<Headless, String> {
+    let renderer = headless_context(width, height)?;
+
+    let mut imgui = Context::create();
+    imgui.set_ini_filename(None);
+    add_fonts(&mut imgui, 1.0);
+    let io = imgui.io_mut();
+    io.display_size = [width as f32, height as f32];
+    io.display_framebuffer_scale = [1.0, 1.0];
+
+    let imgui_renderer = Renderer::init(&mut imgui, &renderer).map_err(|err| format!("Failed to initialize renderer: {}", err))?;
+
+    Ok(Headless {
+        renderer,
+        imgui,
+        imgui_renderer,
+        width,
+        height
+    })
+}
+
+impl Headless {
+    // Draw 'frames' frames into an offscreen buffer, returning the last one as RGBA rows from the top down.
+    // Some things take a few frames to settle down, like the layout of labels and the size of windows, so only the last frame counts.
+    pub fn render<Scene, Draw: FnMut(&Rc<backend::Context>, &mut Scene, &mut SimpleFrameBuffer, &mut Ui)>(&mut self, scene: &mut Scene, frames: usize, mut run_ui: Draw) -> Result<Vec<u8>, String> {
+        let Headless { renderer, imgui, imgui_renderer, width, height } = self;
+        let texture = SrgbTexture2d::empty_with_format(renderer, SrgbFormat::U8U8U8U8, MipmapsOption::NoMipmap, *width, *height)
+            .map_err(|err| format!("Failed to create offscreen buffer: {}", err))?;
+
+        for _ in 0..frames.max(1) {
+            let mut target = SimpleFrameBuffer::new(renderer, &texture).map_err(|err| format!("Failed to create offscreen buffer: {}", err))?;
+            let mut ui = imgui.frame();
+            target.clear_color_srgb(1.0, 1.0, 1.0, 1.0);
+            run_ui(renderer, scene, &mut target, &mut ui);
+            let draw_data = ui.render();
+            imgui_renderer
+                .render(&mut target, draw_data)
+                .map_err(|err| format!("Rendering failed: {}", err))?;
+        }
+
+        // GL counts rows from the bottom up.
+        let image: RawImage2d<u8> = texture.read();
+        let row = 4 * image.width as usize;
+        Ok(image.data.chunks(row).rev().flatten().copied().collect())
+    }
+}
@@ -0,0 +1,262 @@
+use std::borrow::Cow;
+
+use glium::*;
+use glium::backend::Facade;
+use glium::texture::{ClientFormat, MipmapsOption, RawImage2d, Texture2d, UncompressedFloatFormat};
+use rusttype::gpu_cache::Cache;
+use rusttype::{point, Font, PositionedGlyph, Scale};
+
+// The same font that we hand to imgui, so that labels match the rest of the UI.
+const FONT: &[u8] = include_bytes!("../resources/mplus-1p-regular.ttf");
+const FONT_SIZE: f32 = 13.0;
+
+// The size of the glyph atlas. This comfortably fits every glyph we are likely to see in a goal at once,
+// but it doubles in size whenever it runs out of room, up to the largest texture that every GL implementation has to support.
+const CACHE_SIZE: u32 = 1024;
+const MAX_CACHE_SIZE: u32 = 4096;
+
+// The space between text and the edge of its background, in window coordinates.
+const PADDING: f32 = 3.0;
+
+#[derive(Copy, Clone, Debug)]
+pub struct TextVertex {
+    pub position: [f32; 2],
+    pub tex_coords: [f32; 2],
+    pub color: [f32; 4],
+    // 1 for solid backgrounds, 0 for glyphs.
+    pub fill: f32
+}
+
+implement_vertex!(TextVertex, position, tex_coords, color, fill);
+
+struct Queued {
+    glyphs: Vec<PositionedGlyph<'static>>,
+    // The background box, in physical pixels.
+    bounds: [[f32; 2]; 2],
+    color: [f32; 4],
+    background: [f32; 4]
+}
+
+// Draws text straight into the GL scene, using a glyph atlas that gets filled in as new glyphs show up.
+// Text gets queued up over the course of a frame, and then drawn all at once.
+pub struct TextRenderer {
+    font: Font<'static>,
+    cache: Cache<'static>,
+    texture: Texture2d,
+    program: Program,
+    // The ratio of physical pixels to window coordinates.
+    hidpi: f32,
+    queued: Vec<Queued>,
+    // Solid shapes that go underneath all of the text, like the leader lines running out to labels. These are in physical pixels.
+    underlay: Vec<TextVertex>,
+    // Hung on to between frames, and only replaced when there's more to draw than fits.
+    vertex_buffer: Option<VertexBuffer<TextVertex>>
+}
+
+// An empty glyph atlas, 'size' pixels across.
+fn atlas<F: Facade>(display: &F, size: u32) -> Texture2d {
+    Texture2d::with_format(
+        display,
+        RawImage2d {
+            data: Cow::Owned(vec![0u8; (size * size) as usize]),
+            width: size,
+            height: size,
+            format: ClientFormat::U8
+        },
+        UncompressedFloatFormat::U8,
+        MipmapsOption::NoMipmap
+    ).unwrap()
+}
+
+fn solid(position: [f32; 2], color: [f32; 4]) -> TextVertex {
+    TextVertex { position, tex_coords: [0.0, 0.0], color, fill: 1.0 }
+}
+
+impl TextRenderer {
+    pub fn new<F: Facade>(display: &F) -> TextRenderer {
+        let font = Font::try_from_bytes(FONT).expect("Failed to load the label font");
+        let cache = Cache::builder().dimensions(CACHE_SIZE, CACHE_SIZE).build();
+        let texture = atlas(display, CACHE_SIZE);
+
+        let program = program!(display, 140 => {
+            vertex: include_str!("../resources/text.vert"),
+            fragment: include_str!("../resources/text.frag")
+        }).unwrap();
+
+        TextRenderer {
+            font,
+            cache,
+            texture,
+            program,
+            hidpi: 1.0,
+            queued: Vec::new(),
+            underlay: Vec::new(),
+            vertex_buffer: None
+        }
+    }
+
+    // Keep up with the scale of the window, which can change as it moves between monitors.
+    pub fn set_scale(&mut self, hidpi: f32) {
+        self.hidpi = hidpi;
+    }
+
+    // Lay out some text with its top left corner at 'pos', in window coordinates.
+    // The glyphs and the box around them are in physical pixels, so that the text stays crisp on high DPI displays.
+    fn lay_out(&self, pos: [f32; 2], txt: &str) -> (Vec<PositionedGlyph<'static>>, [[f32; 2]; 2]) {
+        let scale = Scale::uniform(FONT_SIZE * self.hidpi);
+        let metrics = self.font.v_metrics(scale);
+        let line_height = metrics.ascent - metrics.descent + metrics.line_gap;
+        let padding = PADDING * self.hidpi;
+        let origin = [pos[0] * self.hidpi + padding, pos[1] * self.hidpi + padding];
+
+        let mut glyphs = Vec::new();
+        let mut width: f32 = 0.0;
+        let mut lines = 0;
+        for (ix, line) in txt.lines().enumerate() {
+            let start = point(origin[0], origin[1] + metrics.ascent + ix as f32 * line_height);
+            for glyph in self.font.layout(line, scale, start) {
+                width = width.max(glyph.position().x - origin[0] + glyph.unpositioned().h_metrics().advance_width);
+                glyphs.push(glyph);
+            }
+            lines += 1;
+        }
+
+        let bounds = [
+            [origin[0] - padding, origin[1] - padding],
+            [origin[0] + width + padding, origin[1] + lines.max(1) as f32 * line_height + padding]
+        ];
+        (glyphs, bounds)
+    }
+
+    // How much room some text takes up, in window coordinates.
+    pub fn measure(&self, txt: &str) -> [f32; 2] {
+        let (_, [min, max]) = self.lay_out([0.0, 0.0], txt);
+        [(max[0] - min[0]) / self.hidpi, (max[1] - min[1]) / self.hidpi]
+    }
+
+    // Queue up some text with its top left corner at 'pos', in window coordinates.
+    // Returns the box that the text will take up, so that it can be clicked on.
+    pub fn queue(&mut self, pos: [f32; 2], txt: &str, color: [f32; 4], background: [f32; 4]) -> [[f32; 2]; 2] {
+        let (glyphs, bounds) = self.lay_out(pos, txt);
+        self.queued.push(Queued { glyphs, bounds, color, background });
+        [
+            [bounds[0][0] / self.hidpi, bounds[0][1] / self.hidpi],
+            [bounds[1][0] / self.hidpi, bounds[1][1] / self.hidpi]
+        ]
+    }
+
+    // Queue up a line running underneath all of the text, in window coordinates.
+    pub fn queue_line(&mut self, from: [f32; 2], to: [f32; 2], width: f32, color: [f32; 4]) {
+        let hidpi = self.hidpi;
+        let [from, to] = [from, to].map(|[x, y]| [x * hidpi, y * hidpi]);
+        let [dx, dy] = [to[0] - from[0], to[1] - from[1]];
+        let length = (dx * dx + dy * dy).sqrt();
+        if length < 1e-3 {
+            return;
+        }
+        // Push the sides of the line out by half its width either way.
+        let half = width * self.hidpi / 2.0;
+        let [nx, ny] = [-dy / length * half, dx / length * half];
+        let [a, b, c, d] = [[from[0] + nx, from[1] + ny], [from[0] - nx, from[1] - ny], [to[0] + nx, to[1] + ny], [to[0] - nx, to[1] - ny]];
+        self.underlay.extend_from_slice(&[solid(a, color), solid(b, color), solid(c, color), solid(c, color), solid(b, color), solid(d, color)]);
+    }
+
+    // Queue up a dot underneath all of the text, or a ring 2 wide if it isn't filled, in window coordinates.
+    pub fn queue_circle(&mut self, center: [f32; 2], radius: f32, color: [f32; 4], filled: bool) {
+        const SEGMENTS: usize = 24;
+        let (inner, outer) = if filled { (0.0, radius) } else { (radius - 1.0, radius + 1.0) };
+        let hidpi = self.hidpi;
+        let at = |r: f32, k: usize| {
+            let angle = 2.0 * std::f32::consts::PI * k as f32 / SEGMENTS as f32;
+            [(center[0] + r * angle.cos()) * hidpi, (center[1] + r * angle.sin()) * hidpi]
+        };
+        for k in 0..SEGMENTS {
+            let [a, b, c, d] = [at(inner, k), at(outer, k), at(inner, k + 1), at(outer, k + 1)];
+            self.underlay.extend_from_slice(&[solid(a, color), solid(b, color), solid(c, color), solid(c, color), solid(b, color), solid(d, color)]);
+        }
+    }
+
+    // Upload any glyphs that aren't in the atlas yet. If they don't all fit, the atlas gets bigger and we try again.
+    // Once it can't get any bigger, the glyphs that didn't fit get left out until there's room for them.
+    fn cache_queued<F: Facade>(&mut self, display: &F) {
+        loop {
+            let texture = &self.texture;
+            let result = self.cache.cache_queued(|rect, data| {
+                texture.main_level().write(
+                    Rect { left: rect.min.x, bottom: rect.min.y, width: rect.width(), height: rect.height() },
+                    RawImage2d { data: Cow::Borrowed(data), width: rect.width(), height: rect.height(), format: ClientFormat::U8 }
+                );
+            });
+            let (size, _) = self.cache.dimensions();
+            match result {
+                Ok(_) => return,
+                Err(_) if size < MAX_CACHE_SIZE => {
+                    // Rebuilding the cache empties it, but keeps the queue, so everything gets uploaded again into the new texture.
+                    self.cache.to_builder().dimensions(size * 2, size * 2).rebuild(&mut self.cache);
+                    self.texture = atlas(display, size * 2);
+                },
+                Err(_) => {
+                    self.cache.clear_queue();
+                    return;
+                }
+            }
+        }
+    }
+
+    // Draw everything that has been queued up this frame, in the order it was queued, on top of the underlay.
+    pub fn draw<F: Facade, S: Surface>(&mut self, display: &F, target: &mut S) {
+        let queued = std::mem::take(&mut self.queued);
+        let underlay = std::mem::take(&mut self.underlay);
+        if queued.is_empty() && underlay.is_empty() {
+            return;
+        }
+
+        for glyph in queued.iter().flat_map(|q| &q.glyphs) {
+            self.cache.queue_glyph(0, glyph.clone());
+        }
+        self.cache_queued(display);
+
+        let mut vertices = underlay;
+        let quad = |vertices: &mut Vec<TextVertex>, min: [f32; 2], max: [f32; 2], uv_min: [f32; 2], uv_max: [f32; 2], color: [f32; 4], fill: f32| {
+            let corner = |x: usize, y: usize| TextVertex {
+                position: [[min[0], max[0]][x], [min[1], max[1]][y]],
+                tex_coords: [[uv_min[0], uv_max[0]][x], [uv_min[1], uv_max[1]][y]],
+                color,
+                fill
+            };
+            vertices.extend_from_slice(&[corner(0, 0), corner(1, 0), corner(0, 1), corner(1, 0), corner(1, 1), corner(0, 1)]);
+        };
+        for q in &queued {
+            quad(&mut vertices, q.bounds[0], q.bounds[1], [0.0, 0.0], [0.0, 0.0], q.background, 1.0);
+            for glyph in &q.glyphs {
+                if let Ok(Some((uv, screen))) = self.cache.rect_for(0, glyph) {
+                    quad(
+                        &mut vertices,
+                        [screen.min.x as f32, screen.min.y as f32],
+                        [screen.max.x as f32, screen.max.y as f32],
+                        [uv.min.x, uv.min.y],
+                        [uv.max.x, uv.max.y],
+                        q.color,
+                        0.0
+                    );
+                }
+            }
+        }
+
+        if !matches!(&self.vertex_buffer, Some(vbo) if vbo.len() >= vertices.len()) {
+            self.vertex_buffer = Some(VertexBuffer::empty_dynamic(display, vertices.len().next_power_of_two()).unwrap());
+        }
+        let vbo = self.vertex_buffer.as_ref().unwrap().slice(0..vertices.len()).unwrap();
+        vbo.write(&vertices);
+        let (width, height) = target.get_dimensions();
+        let uniforms = uniform! {
+            screen: [width as f32, height as f32],
+            glyphs: self.texture.sampled()
+        };
+        let draw_params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+        target.draw(vbo, index::NoIndices(index::PrimitiveType::TrianglesList), &self.program, &uniforms, &draw_params).unwrap();
+    }
+}
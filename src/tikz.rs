@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::geometry::Constraints;
+
+// How wide the picture comes out, in centimetres.
+const WIDTH: f32 = 5.0;
+
+// How close two points need to be to count as the same.
+const EPSILON: f32 = 1e-3;
+
+pub struct Vertex {
+    pub name: String,
+    pub pos: [f32; 2],
+    // Anything that should be written on the vertex itself, rather than next to it.
+    pub txt: Vec<String>
+}
+
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub dim: String,
+    pub color: [f32; 4],
+    pub txt: Vec<String>
+}
+
+pub struct Face {
+    // In the same order as 'geometry::Face', so bottom left, bottom right, top left, top right.
+    pub corners: [usize; 4],
+    pub color: [f32; 4]
+}
+
+// A label off on its own, tied back to 'anchor', with the top left corner of its box at 'pos'.
+pub struct Label {
+    pub anchor: [f32; 2],
+    pub pos: [f32; 2],
+    pub txt: String
+}
+
+// A goal laid out flat, in window coordinates.
+// Squares are drawn like commutative diagrams, with the terms sitting right on their vertices and edges.
+#[derive(Default)]
+pub struct Picture {
+    pub vertices: Vec<Vertex>,
+    pub edges: Vec<Edge>,
+    pub faces: Vec<Face>,
+    pub labels: Vec<Label>,
+    pub square: bool,
+    // The vertices that share each name.
+    copies: HashMap<String, Vec<usize>>
+}
+
+// Name a vertex by its coordinates, so that 'i = 0, j = 1' becomes 'i0-j1'.
+// TikZ is picky about what goes into a name, so anything outside of ASCII gets spelled out.
+pub fn vertex_name(dim_names: &[String], dims: &Constraints) -> String {
+    let parts: Vec<String> = dim_names.iter().filter_map(|nm| {
+        let (_, d) = dims.iter().find(|(dim, _)| dim == nm)?;
+        let nm: String = nm.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_string() } else { format!("u{:x}", c as u32) }).collect();
+        Some(format!("{}{}", nm, if *d { 1 } else { 0 }))
+    }).collect();
+    if parts.is_empty() { "v".to_string() } else { parts.join("-") }
+}
+
+// Digits written as subscripts or superscripts, like in 'a₀' or '𝕊¹'.
+fn script_digit(c: char) -> Option<(char, char)> {
+    const SUBSCRIPTS: [char; 10] = ['₀', '₁', '₂', '₃', '₄', '₅', '₆', '₇', '₈', '₉'];
+    const SUPERSCRIPTS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+    let digit = |ix: usize| std::char::from_digit(ix as u32, 10).unwrap();
+    if let Some(ix) = SUBSCRIPTS.iter().position(|d| *d == c) {
+        Some(('_', digit(ix)))
+    } else {
+        SUPERSCRIPTS.iter().position(|d| *d == c).map(|ix| ('^', digit(ix)))
+    }
+}
+
+// The plain letter or digit behind a double-struck one, like '𝕜' or '𝟙'.
+fn double_struck(c: char) -> Option<char> {
+    let code = c as u32;
+    let offset = |start: u32, base: char| std::char::from_u32(base as u32 + code - start);
+    match c {
+        'ℂ' => Some('C'),
+        'ℍ' => Some('H'),
+        'ℕ' => Some('N'),
+        'ℙ' => Some('P'),
+        'ℚ' => Some('Q'),
+        'ℝ' => Some('R'),
+        'ℤ' => Some('Z'),
+        '\u{1D538}'..='\u{1D551}' => offset(0x1D538, 'A'),
+        '\u{1D552}'..='\u{1D56B}' => offset(0x1D552, 'a'),
+        '\u{1D7D8}'..='\u{1D7E1}' => offset(0x1D7D8, '0'),
+        _ => None
+    }
+}
+
+// Escape a term so that it can go into math mode.
+// The symbols that show up all over cooltt's output get swapped out for macros, so that the result works with pdflatex and amssymb.
+// Anything else that isn't ASCII gets written out as its code point, as pdflatex would refuse it otherwise.
+pub fn math(txt: &str) -> String {
+    let mut out = String::new();
+    let mut chars = txt.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        // Runs of subscript digits have to go into a single subscript, as TeX doesn't allow 'a_1_2'.
+        if let Some((script, digit)) = script_digit(c) {
+            let mut digits = digit.to_string();
+            while let Some((_, digit)) = chars.peek().and_then(|c| script_digit(*c)).filter(|(s, _)| *s == script) {
+                digits.push(digit);
+                chars.next();
+            }
+            write!(out, "{}{{{}}}", script, digits).unwrap();
+            continue;
+        }
+        if let Some(letter) = double_struck(c) {
+            write!(out, "\\mathbb{{{}}}", letter).unwrap();
+            continue;
+        }
+        let macro_name = match c {
+            '\\' => "\\backslash ",
+            '{' => "\\{",
+            '}' => "\\}",
+            '#' => "\\#",
+            '$' => "\\$",
+            '%' => "\\%",
+            '&' => "\\&",
+            '_' => "\\_",
+            '~' => "\\sim ",
+            '^' => "\\hat{}",
+            ' ' => "\\ ",
+            '\n' => "\\ ",
+            '→' => "\\to ",
+            '←' => "\\leftarrow ",
+            '↦' => "\\mapsto ",
+            '×' => "\\times ",
+            '∨' => "\\lor ",
+            '∧' => "\\land ",
+            '¬' => "\\lnot ",
+            '∀' => "\\forall ",
+            '∃' => "\\exists ",
+            '∂' => "\\partial ",
+            '≤' => "\\le ",
+            '≥' => "\\ge ",
+            '≡' => "\\equiv ",
+            '≃' => "\\simeq ",
+            '≅' => "\\cong ",
+            '∘' => "\\circ ",
+            '·' | '∙' => "\\cdot ",
+            '⊎' => "\\uplus ",
+            '⊤' => "\\top ",
+            '⊥' => "\\bot ",
+            '⟨' => "\\langle ",
+            '⟩' => "\\rangle ",
+            'α' => "\\alpha ",
+            'β' => "\\beta ",
+            'γ' => "\\gamma ",
+            'δ' => "\\delta ",
+            'ε' => "\\varepsilon ",
+            'θ' => "\\theta ",
+            'λ' => "\\lambda ",
+            'μ' => "\\mu ",
+            'π' => "\\pi ",
+            'σ' => "\\sigma ",
+            'τ' => "\\tau ",
+            'φ' => "\\varphi ",
+            'ψ' => "\\psi ",
+            'ω' => "\\omega ",
+            'Γ' => "\\Gamma ",
+            'Δ' => "\\Delta ",
+            'Λ' => "\\Lambda ",
+            'Σ' => "\\Sigma ",
+            'Π' => "\\Pi ",
+            'Ω' => "\\Omega ",
+            c if c.is_ascii() => {
+                out.push(c);
+                continue;
+            },
+            c => {
+                write!(out, "\\mathtt{{U{{+}}{:04X}}}", c as u32).unwrap();
+                continue;
+            }
+        };
+        out.push_str(macro_name);
+    }
+    out.trim_end().to_string()
+}
+
+// A term in math mode. Empty terms are left out entirely, as '$$' means something else to TeX.
+fn node_text(txt: &str) -> String {
+    if txt.is_empty() { String::new() } else { format!("${}$", txt) }
+}
+
+// Set the colour of whatever 'key' is, like 'draw' or 'fill', along with its opacity.
+fn color(key: &str, color: [f32; 4]) -> String {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let mut style = format!("{}={{rgb,255:red,{};green,{};blue,{}}}", key, channel(color[0]), channel(color[1]), channel(color[2]));
+    if color[3] < 1.0 {
+        write!(style, ", {} opacity={:.2}", key, color[3]).unwrap();
+    }
+    style
+}
+
+fn close(a: [f32; 2], b: [f32; 2]) -> bool {
+    (a[0] - b[0]).abs() <= EPSILON && (a[1] - b[1]).abs() <= EPSILON
+}
+
+impl Picture {
+    pub fn new(square: bool) -> Picture {
+        Picture { square, ..Default::default() }
+    }
+
+    fn extent(&self) -> ([f32; 2], f32) {
+        let points = self.vertices.iter().map(|v| v.pos);
+        let min = points.clone().fold([f32::INFINITY; 2], |acc, p| [acc[0].min(p[0]), acc[1].min(p[1])]);
+        let max = points.fold([f32::NEG_INFINITY; 2], |acc, p| [acc[0].max(p[0]), acc[1].max(p[1])]);
+        if self.vertices.is_empty() {
+            return ([0.0, 0.0], 1.0);
+        }
+        (min, (max[0] - min[0]).max(max[1] - min[1]).max(f32::EPSILON))
+    }
+
+    // Find the vertex at 'pos' with the given coordinates, adding it if we haven't seen it before.
+    // Nets have several copies of the same vertex, which get told apart by a suffix.
+    pub fn vertex(&mut self, dim_names: &[String], dims: &Constraints, pos: [f32; 2]) -> usize {
+        let name = vertex_name(dim_names, dims);
+        let vertices = &self.vertices;
+        let copies = self.copies.entry(name.clone()).or_default();
+        if let Some(ix) = copies.iter().find(|ix| close(vertices[**ix].pos, pos)) {
+            return *ix;
+        }
+        let name = if copies.is_empty() { name } else { format!("{}-{}", name, copies.len() + 1) };
+        copies.push(self.vertices.len());
+        self.vertices.push(Vertex { name, pos, txt: Vec::new() });
+        self.vertices.len() - 1
+    }
+
+    // Put a label onto whatever is at 'pos'. Labels that land on a vertex or the middle of an edge get written right onto it.
+    // Returns false if there was nothing there, and the label needs to go somewhere else.
+    pub fn attach(&mut self, pos: [f32; 2], txt: &str) -> bool {
+        if let Some(vertex) = self.vertices.iter_mut().find(|v| close(v.pos, pos)) {
+            vertex.txt.push(txt.to_string());
+            return true;
+        }
+        let vertices = &self.vertices;
+        let middle = |edge: &Edge| {
+            let [from, to] = [vertices[edge.from].pos, vertices[edge.to].pos];
+            [(from[0] + to[0]) / 2.0, (from[1] + to[1]) / 2.0]
+        };
+        if let Some(edge) = self.edges.iter_mut().find(|edge| close(middle(edge), pos)) {
+            edge.txt.push(txt.to_string());
+            return true;
+        }
+        false
+    }
+
+    pub fn to_tikz(&self) -> String {
+        let (min, extent) = self.extent();
+        let scale = WIDTH / extent;
+        // Window coordinates go down the screen, but TikZ goes up the page.
+        let at = |p: [f32; 2]| format!("({:.3}, {:.3})", (p[0] - min[0]) * scale, (min[1] - p[1]) * scale);
+        let terms = |txt: &[String]| txt.iter().map(|t| math(t)).collect::<Vec<String>>().join(",\\ ");
+
+        let mut out = String::new();
+        writeln!(out, "% Drawn by coolttviz. This needs the tikz and amssymb packages.").unwrap();
+        writeln!(out, "\\begin{{tikzpicture}}[").unwrap();
+        writeln!(out, "    edge label/.style={{midway, auto, font=\\scriptsize}},").unwrap();
+        writeln!(out, "    label/.style={{anchor=north west, fill=white, fill opacity=0.8, text opacity=1, inner sep=2pt}},").unwrap();
+        writeln!(out, "    leader/.style={{very thin}},").unwrap();
+        writeln!(out, "  ]").unwrap();
+
+        for v in &self.vertices {
+            if self.square {
+                writeln!(out, "  \\node ({}) at {} {{{}}};", v.name, at(v.pos), node_text(&terms(&v.txt))).unwrap();
+            } else {
+                writeln!(out, "  \\coordinate ({}) at {};", v.name, at(v.pos)).unwrap();
+            }
+        }
+
+        for face in &self.faces {
+            let [bl, br, tl, tr] = face.corners;
+            let name = |ix: usize| &self.vertices[ix].name;
+            writeln!(
+                out,
+                "  \\fill[{}] ({}) -- ({}) -- ({}) -- ({}) -- cycle;",
+                color("fill", face.color), name(bl), name(br), name(tr), name(tl)
+            ).unwrap();
+        }
+
+        for edge in &self.edges {
+            // Squares have room to write the terms on their edges, along with arrows running from 0 to 1.
+            let (arrow, txt) =
+                if self.square && !edge.txt.is_empty() {
+                    ("->, ", terms(&edge.txt))
+                } else if self.square {
+                    ("->, ", math(&edge.dim))
+                } else {
+                    ("", math(&edge.dim))
+                };
+            writeln!(
+                out,
+                "  \\draw[{}{}] ({}) -- node[edge label] {{{}}} ({});",
+                arrow, color("draw", edge.color), self.vertices[edge.from].name, node_text(&txt), self.vertices[edge.to].name
+            ).unwrap();
+        }
+
+        for (ix, lbl) in self.labels.iter().enumerate() {
+            if self.square {
+                // Whatever doesn't fit on a vertex or an edge sits in the middle of the square.
+                writeln!(out, "  \\node at {} {{{}}};", at(lbl.anchor), node_text(&math(&lbl.txt))).unwrap();
+            } else {
+                writeln!(out, "  \\node[label] (label{}) at {} {{{}}};", ix, at(lbl.pos), node_text(&math(&lbl.txt))).unwrap();
+                writeln!(out, "  \\draw[leader] {} -- (label{});", at(lbl.anchor), ix).unwrap();
+                writeln!(out, "  \\fill {} circle (1pt);", at(lbl.anchor)).unwrap();
+            }
+        }
+
+        writeln!(out, "\\end{{tikzpicture}}").unwrap();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::fixtures;
+
+    #[test]
+    fn names() {
+        let dim_names = fixtures::names(&["i", "j", "𝕜"]);
+        assert_eq!(vertex_name(&dim_names, &vec![("j".to_string(), true), ("i".to_string(), false)]), "i0-j1");
+        assert_eq!(vertex_name(&dim_names, &vec![("𝕜".to_string(), true)]), "u1d55c1");
+        assert_eq!(vertex_name(&dim_names, &vec![]), "v");
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(math("λ i → p_0 {x}"), "\\lambda \\ i\\ \\to \\ p\\_0\\ \\{x\\}");
+        assert_eq!(math("50% & #1"), "50\\%\\ \\&\\ \\#1");
+        assert_eq!(math("a₀ a₁₂ 𝕊¹"), "a_{0}\\ a_{12}\\ \\mathbb{S}^{1}");
+        assert_eq!(math("𝕜 𝟙 ℕ"), "\\mathbb{k}\\ \\mathbb{1}\\ \\mathbb{N}");
+        assert_eq!(math("☃"), "\\mathtt{U{+}2603}");
+    }
+
+    #[test]
+    fn cooltt_goals_are_ascii() {
+        // Something cooltt might well send us, which has to come out as plain ASCII for pdflatex.
+        let goal = "λ 𝕚 𝕛 → hcom 𝕀 𝟘 𝟙 {∂ 𝕚 ∨ 𝕛 = 𝟙} (λ 𝕜 → [𝕜 = 𝟘 ∨ ∂ 𝕚 ↦ p₀ 𝕛, 𝕛 = 𝟙 ↦ q₁]) : ℕ × (𝔹 → 𝕊¹) ⊎ Σ (x : A) (B x)";
+        let tex = math(goal);
+        assert!(tex.is_ascii(), "{} isn't ASCII", tex);
+        assert!(tex.contains("\\mathbb{k}\\ \\to \\ [\\mathbb{k}\\ =\\ \\mathbb{0}"));
+        assert!(tex.contains("p_{0}\\ \\mathbb{j}"));
+        assert!(tex.contains("\\uplus \\ \\Sigma "));
+    }
+
+    #[test]
+    fn square() {
+        let dim_names = fixtures::names(&["i", "j"]);
+        let at = |i: bool, j: bool| vec![("i".to_string(), i), ("j".to_string(), j)];
+        let mut picture = Picture::new(true);
+        let a = picture.vertex(&dim_names, &at(false, false), [0.0, 1.0]);
+        let b = picture.vertex(&dim_names, &at(true, false), [1.0, 1.0]);
+        assert_eq!(picture.vertex(&dim_names, &at(false, false), [0.0, 1.0]), a);
+        picture.edges.push(Edge { from: a, to: b, dim: "i".to_string(), color: [0.0, 0.0, 0.0, 1.0], txt: Vec::new() });
+
+        assert!(picture.attach([0.0, 1.0], "a₀"));
+        assert!(picture.attach([0.5, 1.0], "p i"));
+        assert!(!picture.attach([0.5, 0.5], "sq"));
+
+        let tikz = picture.to_tikz();
+        assert!(tikz.contains("\\node (i0-j0) at (0.000, 0.000) {$a_{0}$};"));
+        assert!(tikz.contains("\\node (i1-j0) at (5.000, 0.000) {};"));
+        assert!(tikz.contains("\\draw[->, draw={rgb,255:red,0;green,0;blue,0}] (i0-j0) -- node[edge label] {$p\\ i$} (i1-j0);"));
+    }
+
+    #[test]
+    fn nets_have_copies() {
+        let dim_names = fixtures::names(&["i"]);
+        let mut picture = Picture::new(false);
+        let a = picture.vertex(&dim_names, &vec![("i".to_string(), false)], [0.0, 0.0]);
+        let b = picture.vertex(&dim_names, &vec![("i".to_string(), false)], [10.0, 0.0]);
+        let c = picture.vertex(&dim_names, &vec![("i".to_string(), false)], [20.0, 0.0]);
+        assert_eq!(picture.vertices[a].name, "i0");
+        assert_eq!(picture.vertices[b].name, "i0-2");
+        assert_eq!(picture.vertices[c].name, "i0-3");
+        assert_eq!(picture.vertex(&dim_names, &vec![("i".to_string(), false)], [10.0, 0.0]), b);
+    }
+}
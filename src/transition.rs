@@ -0,0 +1,69 @@
+use nalgebra::Vector3;
+
+use crate::linalg::{Embedding, Project};
+
+// How long it takes to move from one goal to the next, in seconds.
+pub const DURATION: f32 = 0.5;
+
+// Start and finish slowly, so that it is easier to follow what is moving where.
+pub fn ease(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// Move a point between goals by matching up the names of the dimensions.
+// Dimensions that the old goal didn't have get put in the middle of the cube.
+pub fn lift(from: &[String], to: &[String], v: &[f32]) -> Vec<f32> {
+    to.iter().map(|nm| {
+        from.iter().position(|dim| dim == nm).and_then(|ix| v.get(ix).copied()).unwrap_or(0.0)
+    }).collect()
+}
+
+pub fn lerp(from: &[f32], to: &[f32], t: f32) -> Vec<f32> {
+    from.iter().zip(to).map(|(a, b)| a + t * (b - a)).collect()
+}
+
+// Projects points of the new goal partway between where the old embedding would put them and where the new one does.
+pub struct Morph<'a> {
+    pub from: &'a Embedding,
+    pub from_dims: &'a [String],
+    pub to: &'a Embedding,
+    pub to_dims: &'a [String],
+    pub t: f32
+}
+
+impl<'a> Project for Morph<'a> {
+    fn project(&self, v: &[f32]) -> Vector3<f32> {
+        let start = self.from.project(&lift(self.to_dims, self.from_dims, v));
+        let end = self.to.project(v);
+        start + ease(self.t) * (end - start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::fixtures::names;
+
+    #[test]
+    fn lift_by_name() {
+        let from = names(&["i", "j", "k"]);
+        let to = names(&["k", "l", "i"]);
+        assert_eq!(lift(&from, &to, &[1.0, -1.0, 0.5]), vec![0.5, 0.0, 1.0]);
+        assert_eq!(lift(&to, &from, &[0.5, 0.0, 1.0]), vec![1.0, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn morph_endpoints() {
+        let from_dims = names(&["i", "j", "k"]);
+        let to_dims = names(&["i", "j", "k", "l"]);
+        let from = Embedding::default();
+        let to = Embedding { axes: vec![3, 0, 1, 2], ..Embedding::default() };
+        let v = [1.0, -1.0, 1.0, 1.0];
+
+        let start = Morph { from: &from, from_dims: &from_dims, to: &to, to_dims: &to_dims, t: 0.0 };
+        assert!((start.project(&v) - from.project(&[1.0, -1.0, 1.0])).norm() < 1e-6);
+        let end = Morph { t: 1.0, ..start };
+        assert!((end.project(&v) - to.project(&v)).norm() < 1e-6);
+    }
+}
@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+// These all fail before there's any need for a GL context, so they work anywhere.
+fn coolttviz(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_coolttviz-rs")).args(args).output().expect("Failed to run coolttviz")
+}
+
+fn scratch(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("coolttviz-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn bad_arguments() {
+    let output = coolttviz(&["--render", "goal.json"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--render needs an --out"), "{}", stderr);
+    assert!(stderr.contains("Usage:"), "{}", stderr);
+
+    let output = coolttviz(&["--render", "goal.json", "--out", "goal.png", "--size", "800by600"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn missing_goal() {
+    let output = coolttviz(&["--render", "there-is-no-goal-here.json", "--out", "goal.png"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Couldn't read there-is-no-goal-here.json"), "{}", stderr);
+    assert!(!stderr.contains("Usage:"), "{}", stderr);
+}
+
+#[test]
+fn bad_goals() {
+    let out = std::env::temp_dir().join("coolttviz-bad-goal.png");
+    for (name, contents, error) in [
+        ("garbage.json", "{ not json", "Couldn't parse"),
+        ("empty.json", "[]", "should start with a goal")
+    ] {
+        let goal = scratch(name, contents);
+        let output = coolttviz(&["--render", goal.to_str().unwrap(), "--out", out.to_str().unwrap()]);
+        std::fs::remove_file(&goal).unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(error), "{}", stderr);
+        assert!(!out.exists());
+    }
+}